@@ -1,27 +1,43 @@
-use crate::Result;
+use crate::MultiQueueTun;
 #[cfg(target_os = "linux")]
 use crate::linux::params::Params;
 #[cfg(target_os = "linux")]
 use crate::tun::Tun;
-use core::convert::From;
-use libc::{IFF_NO_PI, IFF_TAP, IFF_TUN};
-use std::net::Ipv4Addr;
+use crate::{Error, InterfaceFlags, IpNetwork, NetNs, OffloadFlags, Result, Route};
+use core::convert::TryFrom;
+use libc::{IFF_MULTI_QUEUE, IFF_NO_PI, IFF_TAP, IFF_TUN, IFF_VNET_HDR};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::unix::net::UnixStream;
 
 /// Represents a factory to build new instances of [`Tun`](struct.Tun.html).
 pub struct TunBuilder {
     name: String,
     is_tap: bool,
     packet_info: bool,
+    vnet_hdr: bool,
+    offload: Option<OffloadFlags>,
     persist: bool,
+    attach: bool,
     up: bool,
+    interface_flags: InterfaceFlags,
     mtu: Option<i32>,
+    txqueuelen: Option<u32>,
     owner: Option<i32>,
     group: Option<i32>,
+    mac_address: Option<[u8; 6]>,
     address: Option<Ipv4Addr>,
     destination: Option<Ipv4Addr>,
     broadcast: Option<Ipv4Addr>,
     netmask: Option<Ipv4Addr>,
+    /// Addresses whose prefix length is invalid are reported when building.
+    addresses: Vec<Result<IpNetwork>>,
+    routes: Vec<Route>,
+    route_table: Option<u32>,
+    route_metric: Option<u32>,
+    remove_routes: bool,
     queues: Option<usize>,
+    netns: Option<NetNs>,
+    queue_stats: bool,
     cloexec: bool,
 }
 
@@ -33,14 +49,27 @@ impl Default for TunBuilder {
             group: None,
             is_tap: false,
             persist: false,
+            attach: false,
             up: false,
+            interface_flags: InterfaceFlags::empty(),
             mtu: None,
+            txqueuelen: None,
             packet_info: false,
+            vnet_hdr: false,
+            offload: None,
+            mac_address: None,
             address: None,
             destination: None,
             broadcast: None,
             netmask: None,
+            addresses: Vec::new(),
+            routes: Vec::new(),
+            route_table: None,
+            route_metric: None,
+            remove_routes: false,
             queues: None,
+            netns: None,
+            queue_stats: false,
             cloexec: true,
         }
     }
@@ -52,7 +81,11 @@ impl TunBuilder {
         Default::default()
     }
 
-    /// Sets the name of device (max length: 16 characters), if it is empty, then device name is set by kernel. Default value is empty.
+    /// Sets the name of device (max length: 15 bytes), if it is empty, then device name is set by kernel. Default value is empty.
+    ///
+    /// The name may also be a template such as `tun%d`, in which case the kernel replaces `%d`
+    /// with the first free index and [`Tun::name`](struct.Tun.html#method.name) returns the
+    /// resolved name. Names containing `/`, `:` or whitespace are rejected when building.
     pub fn name(mut self, name: &str) -> Self {
         self.name = name.into();
         self
@@ -93,6 +126,28 @@ impl TunBuilder {
         self
     }
 
+    /// Enables vnet headers by setting the `IFF_VNET_HDR` flag on allocation.
+    ///
+    /// Every packet read from or written to the device is then prefixed by a
+    /// [`VirtioNetHdr`](struct.VirtioNetHdr.html) carrying checksum offload and segmentation
+    /// metadata. Use [`Tun::recv_vnet`](struct.Tun.html#method.recv_vnet) and
+    /// [`Tun::send_vnet`](struct.Tun.html#method.send_vnet) to split and prepend it.
+    pub fn vnet_hdr(mut self) -> Self {
+        self.vnet_hdr = true;
+        self
+    }
+
+    /// Sets the offloads (e.g. `OffloadFlags::CSUM | OffloadFlags::TSO4`) which the kernel may
+    /// use when passing packets to the device.
+    ///
+    /// With TCP/UDP segmentation offloads, the device receives super-packets of up to 64 KiB
+    /// instead of MTU-sized packets. This requires [`vnet_hdr`](#method.vnet_hdr), otherwise
+    /// building the device fails.
+    pub fn offload(mut self, offload: OffloadFlags) -> Self {
+        self.offload = Some(offload);
+        self
+    }
+
     /// Sets the MTU (Maximum Transfer Unit) of device.
     ///
     /// MTU defines the maximum size of packets which this device will allow being transmitted or
@@ -105,6 +160,13 @@ impl TunBuilder {
         self
     }
 
+    /// Sets the length of the transmit queue of device, i.e. the number of packets the kernel
+    /// buffers before dropping them while the application does not read fast enough.
+    pub fn txqueuelen(mut self, len: u32) -> Self {
+        self.txqueuelen = Some(len);
+        self
+    }
+
     /// Sets the owner of device.
     ///
     /// This is the numeric UID of the user who will own the created device.
@@ -121,10 +183,19 @@ impl TunBuilder {
         self
     }
 
+    /// Sets the MAC address of device.
+    ///
+    /// This is only supported by TAP devices, building a TUN device with a MAC address fails.
+    pub fn mac_address(mut self, mac: [u8; 6]) -> Self {
+        self.mac_address = Some(mac);
+        self
+    }
+
     /// Sets IPv4 address of device.
     ///
     /// Sending packets to this address is how they are delivered to your program.
-    /// This includes routing via this address.
+    /// This includes routing via this address. Without a [`netmask`](#method.netmask), the
+    /// netmask is the one of the address class (`/32` for Tun devices), as with `ifconfig`.
     pub fn address(mut self, address: Ipv4Addr) -> Self {
         self.address = Some(address);
         self
@@ -154,6 +225,114 @@ impl TunBuilder {
         self
     }
 
+    /// Adds an IPv6 address with the given prefix length (e.g. `64`) to device.
+    ///
+    /// Unlike the IPv4 settings, this may be called multiple times to assign several IPv6
+    /// addresses. This is a shorthand for [`add_address`](#method.add_address), building fails if
+    /// `prefix_len` is greater than `128`.
+    pub fn ipv6_address(mut self, address: Ipv6Addr, prefix_len: u8) -> Self {
+        self.addresses.push(IpNetwork::new(address, prefix_len));
+        self
+    }
+
+    /// Adds an IPv4 or IPv6 address along with its prefix length to device.
+    ///
+    /// This may be called multiple times: the first address of each family becomes the primary
+    /// address and the following ones are added as secondary addresses. Without netlink, only a
+    /// single IPv4 address is supported.
+    pub fn add_address(mut self, address: IpNetwork) -> Self {
+        self.addresses.push(Ok(address));
+        self
+    }
+
+    /// Adds an IPv4 or IPv6 route through device, e.g. `0.0.0.0/0` for a default route or a
+    /// split-tunnel prefix.
+    ///
+    /// May be called multiple times. Routes are added once the device is configured, so IPv4
+    /// routes require the device to be [`up`](#method.up).
+    ///
+    /// ```no_run
+    /// use tokio_tun::Tun;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let tun = Tun::builder()
+    ///     .name("tun0")
+    ///     .address("10.8.0.2".parse().unwrap())
+    ///     .up()
+    ///     .route("10.8.0.0/16".parse::<tokio_tun::IpNetwork>().unwrap())
+    ///     .route("fd00:8::/64".parse::<tokio_tun::IpNetwork>().unwrap())
+    ///     .route_table(100)
+    ///     .remove_routes_on_drop()
+    ///     .build()
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn route(mut self, route: impl Into<Route>) -> Self {
+        self.routes.push(route.into());
+        self
+    }
+
+    /// Sets the routing table of the [`route`](#method.route)s which do not specify one,
+    /// instead of the main table.
+    pub fn route_table(mut self, table: u32) -> Self {
+        self.route_table = Some(table);
+        self
+    }
+
+    /// Sets the metric of the [`route`](#method.route)s which do not specify one.
+    pub fn route_metric(mut self, metric: u32) -> Self {
+        self.route_metric = Some(metric);
+        self
+    }
+
+    /// Removes the [`route`](#method.route)s, as well as the ones added using
+    /// [`Tun::add_route`](struct.Tun.html#method.add_route), when the last queue of the device
+    /// is dropped.
+    ///
+    /// The kernel removes the routes along with the device anyway, so this is mostly useful for
+    /// [`persist`](#method.persist)ent devices.
+    pub fn remove_routes_on_drop(mut self) -> Self {
+        self.remove_routes = true;
+        self
+    }
+
+    /// Creates the device inside the network namespace `ns`, given by a path (e.g.
+    /// `/var/run/netns/<name>`) or an open file descriptor.
+    ///
+    /// Only the building thread temporarily switches to the namespace, which requires
+    /// `CAP_SYS_ADMIN`. The device is configured inside the namespace and the returned instances
+    /// keep working from the namespace of the caller.
+    ///
+    /// ```no_run
+    /// use tokio_tun::Tun;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let tun = Tun::builder()
+    ///     .name("tun0")
+    ///     .netns("/var/run/netns/test")
+    ///     .up()
+    ///     .build()
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn netns(mut self, ns: impl Into<NetNs>) -> Self {
+        self.netns = Some(ns.into());
+        self
+    }
+
+    /// Keeps packet, byte and error counters for every queue in the process, see
+    /// [`Tun::queue_stats`](struct.Tun.html#method.queue_stats).
+    ///
+    /// Comparing them with [`Tun::stats`](struct.Tun.html#method.stats) tells apart packets
+    /// dropped by the kernel from those lost in the process. Queues opened later using
+    /// [`Tun::new_queue`](struct.Tun.html#method.new_queue) keep counters as well.
+    pub fn queue_stats(mut self) -> Self {
+        self.queue_stats = true;
+        self
+    }
+
     /// Removes CLOEXEC flag on all FDs. This will allow passing tun/tap FDs to any exec-ed
     /// child processes.
     /// Default behaviour is to prevent passing fds flag.
@@ -183,6 +362,25 @@ impl TunBuilder {
         self
     }
 
+    /// Attaches to an existing (e.g. persistent) device instead of creating a new one.
+    ///
+    /// Only the name, type ([`tap`](#method.tap)), [`queues`](#method.queues),
+    /// [`packet_info`](#method.packet_info) and [`vnet_hdr`](#method.vnet_hdr) options are used:
+    /// the device is not reconfigured, so its current MTU, addresses, owner, etc. are kept and can
+    /// be read from the returned instances. This allows an unprivileged process to use a device
+    /// created by an administrator with the matching [`owner`](#method.owner) or
+    /// [`group`](#method.group).
+    ///
+    /// Building fails if no device with the given name exists, or if its type, queue mode,
+    /// packet information or vnet header setting differs from the requested one, since changing
+    /// them would break the framing of the queues already attached. Use
+    /// [`Tun::attach`](struct.Tun.html#method.attach) to take these settings from the device.
+    /// Inside a [`netns`](#method.netns), only the type and queue mode can be verified.
+    pub fn attach(mut self) -> Self {
+        self.attach = true;
+        self
+    }
+
     /// Sets up the device.
     ///
     /// This means the interface is immediately put into the *up* state.
@@ -192,19 +390,62 @@ impl TunBuilder {
         self
     }
 
+    /// Sets the given interface flags (e.g. `InterfaceFlags::PROMISC`) on the device.
+    ///
+    /// May be called multiple times, the flags are accumulated. Read-only flags such as
+    /// `LOWER_UP` are ignored by the kernel.
+    pub fn flags(mut self, flags: InterfaceFlags) -> Self {
+        self.interface_flags |= flags;
+        self
+    }
+
     /// Builds a new instance of [`Tun`](struct.Tun.html).
     pub fn build(self) -> Result<Vec<Tun>> {
         match self.queues {
-            Some(queues) if queues > 1 => Tun::new_mq(self.into(), queues),
-            _ => Tun::new(self.into()).map(|tun| vec![tun]),
+            Some(queues) if queues > 1 => Tun::new_mq(self.try_into()?, queues),
+            _ => Tun::new(self.try_into()?).map(|tun| vec![tun]),
+        }
+    }
+
+    /// Builds a new instance of [`MultiQueueTun`](struct.MultiQueueTun.html) with
+    /// `IFF_MULTI_QUEUE` flag, owning all the queues set by [`queues`](#method.queues).
+    ///
+    /// Unlike [`build`](#method.build), the flag is set even for a single queue, so that more
+    /// queues can be added later.
+    pub fn build_mq(self) -> Result<MultiQueueTun> {
+        let (params, queues) = self.into_mq_params()?;
+        Tun::new_mq(params, queues).map(MultiQueueTun::new)
+    }
+
+    /// Builds the device and sends its queues over `stream` (using `SCM_RIGHTS`) instead of
+    /// returning them, so that another process can use them after calling
+    /// [`Tun::receive_from`](struct.Tun.html#method.receive_from).
+    ///
+    /// The queues are closed in the calling process. This does not require a tokio runtime,
+    /// which suits a small privileged helper creating devices on behalf of an unprivileged
+    /// process.
+    pub fn send_to(self, stream: &UnixStream) -> Result<()> {
+        match self.queues {
+            Some(queues) if queues > 1 => Tun::send_to(self.try_into()?, queues, stream),
+            _ => Tun::send_to(self.try_into()?, 1, stream),
         }
     }
+
+    /// Returns the parameters of a multi-queue device along with its number of queues.
+    pub(crate) fn into_mq_params(self) -> Result<(Params, usize)> {
+        let queues = self.queues.unwrap_or(1).max(1);
+        let mut params = Params::try_from(self)?;
+        params.flags |= IFF_MULTI_QUEUE as i16;
+        Ok((params, queues))
+    }
 }
 
-impl From<TunBuilder> for Params {
+impl TryFrom<TunBuilder> for Params {
+    type Error = Error;
+
     #[cfg(target_os = "linux")]
-    fn from(builder: TunBuilder) -> Self {
-        Params {
+    fn try_from(builder: TunBuilder) -> Result<Self> {
+        Ok(Params {
             name: if builder.name.is_empty() {
                 None
             } else {
@@ -215,23 +456,44 @@ impl From<TunBuilder> for Params {
                 if !builder.packet_info {
                     flags |= IFF_NO_PI as i16;
                 }
+                if builder.vnet_hdr {
+                    flags |= IFF_VNET_HDR as i16;
+                }
                 flags
             },
             persist: builder.persist,
+            attach: builder.attach,
             up: builder.up,
+            offload: builder.offload,
+            interface_flags: builder.interface_flags,
             mtu: builder.mtu,
+            txqueuelen: builder.txqueuelen,
             owner: builder.owner,
             group: builder.group,
+            mac_address: builder.mac_address,
             address: builder.address,
             destination: builder.destination,
             broadcast: builder.broadcast,
             netmask: builder.netmask,
+            addresses: builder.addresses.into_iter().collect::<Result<_>>()?,
+            routes: builder
+                .routes
+                .into_iter()
+                .map(|route| Route {
+                    table: route.table.or(builder.route_table),
+                    metric: route.metric.or(builder.route_metric),
+                    ..route
+                })
+                .collect(),
+            remove_routes: builder.remove_routes,
+            netns: builder.netns,
+            queue_stats: builder.queue_stats,
             cloexec: builder.cloexec,
-        }
+        })
     }
 
     #[cfg(not(any(target_os = "linux")))]
-    fn from(builder: TunBuilder) -> Self {
+    fn try_from(builder: TunBuilder) -> Result<Self> {
         unimplemented!()
     }
 }
@@ -0,0 +1,183 @@
+use crate::IpNetwork;
+use crate::linux::netlink::{self, Messages};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::io;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::unix::AsyncFd;
+
+/// Represents a change of a Tun/Tap device made by the kernel or another process, e.g. using
+/// `ip link set tun0 down`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterfaceEvent {
+    /// The device was brought up.
+    Up,
+    /// The device was brought down.
+    Down,
+    /// The carrier (i.e. the `LOWER_UP` flag) changed, which for Tun/Tap devices means a queue
+    /// was attached (`true`) or all queues were detached (`false`).
+    Carrier(bool),
+    /// The MTU changed.
+    MtuChanged(u32),
+    /// An address was added, or an existing one was updated (e.g. once an IPv6 address passed
+    /// duplicate address detection).
+    AddressAdded(IpNetwork),
+    /// An address was removed.
+    AddressRemoved(IpNetwork),
+    /// The device was renamed.
+    Renamed(String),
+    /// The device was deleted, or moved to another network namespace. This is the last event.
+    Deleted,
+}
+
+/// Represents the state of the link the events are derived from.
+struct LinkState {
+    name: Option<String>,
+    flags: u32,
+    mtu: Option<u32>,
+}
+
+/// Represents a stream of [`InterfaceEvent`](enum.InterfaceEvent.html)s of a device, returned
+/// by [`Tun::events`](struct.Tun.html#method.events).
+///
+/// Events are received through a netlink multicast subscription and filtered by the index of
+/// the device. The stream yields an error if the kernel dropped notifications because they were
+/// not consumed fast enough, and ends after [`Deleted`](enum.InterfaceEvent.html#variant.Deleted).
+pub struct InterfaceEvents {
+    socket: AsyncFd<OwnedFd>,
+    index: u32,
+    link: LinkState,
+    pending: VecDeque<InterfaceEvent>,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl InterfaceEvents {
+    /// Creates an instance from a subscribed netlink socket and the current attributes of the
+    /// link. Must be called within a tokio runtime.
+    pub(crate) fn new(socket: OwnedFd, link: netlink::Link) -> io::Result<Self> {
+        // SAFETY: the `AsyncFd` owns `socket` and keeps it open until it is dropped.
+        let socket = unsafe { AsyncFd::register(socket) }.map_err(|err| err.into_parts().1)?;
+        Ok(Self {
+            socket,
+            index: link.index,
+            link: LinkState {
+                name: link.name,
+                flags: link.flags,
+                mtu: link.mtu,
+            },
+            pending: VecDeque::new(),
+            buf: vec![0; netlink::RECV_BUF_LEN],
+            done: false,
+        })
+    }
+
+    /// Receives the next event, returning `None` once the device is deleted.
+    pub async fn recv(&mut self) -> Option<io::Result<InterfaceEvent>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    fn handle(&mut self, len: usize) {
+        for (hdr, payload) in Messages::new(&self.buf[..len]) {
+            match hdr.nlmsg_type {
+                libc::RTM_NEWLINK => {
+                    let Some(link) = netlink::parse_link(payload) else {
+                        continue;
+                    };
+                    if link.index != self.index {
+                        continue;
+                    }
+                    if let Some(name) = link
+                        .name
+                        .as_ref()
+                        .filter(|&name| self.link.name.as_ref() != Some(name))
+                    {
+                        self.pending
+                            .push_back(InterfaceEvent::Renamed(name.clone()));
+                    }
+                    let changed = link.flags ^ self.link.flags;
+                    if changed & libc::IFF_UP as u32 != 0 {
+                        self.pending
+                            .push_back(if link.flags & libc::IFF_UP as u32 != 0 {
+                                InterfaceEvent::Up
+                            } else {
+                                InterfaceEvent::Down
+                            });
+                    }
+                    if changed & libc::IFF_LOWER_UP as u32 != 0 {
+                        self.pending.push_back(InterfaceEvent::Carrier(
+                            link.flags & libc::IFF_LOWER_UP as u32 != 0,
+                        ));
+                    }
+                    if let Some(mtu) = link.mtu.filter(|&mtu| Some(mtu) != self.link.mtu) {
+                        self.pending.push_back(InterfaceEvent::MtuChanged(mtu));
+                    }
+                    self.link = LinkState {
+                        name: link.name.or(self.link.name.take()),
+                        flags: link.flags,
+                        mtu: link.mtu.or(self.link.mtu),
+                    };
+                }
+                libc::RTM_DELLINK
+                    if netlink::parse_link(payload)
+                        .is_some_and(|link| link.index == self.index) =>
+                {
+                    self.pending.push_back(InterfaceEvent::Deleted);
+                }
+                libc::RTM_NEWADDR | libc::RTM_DELADDR => {
+                    let Some(address) = netlink::parse_address(payload) else {
+                        continue;
+                    };
+                    if address.index != self.index {
+                        continue;
+                    }
+                    self.pending
+                        .push_back(if hdr.nlmsg_type == libc::RTM_NEWADDR {
+                            InterfaceEvent::AddressAdded(address.local)
+                        } else {
+                            InterfaceEvent::AddressRemoved(address.local)
+                        });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Stream for InterfaceEvents {
+    type Item = io::Result<InterfaceEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                if event == InterfaceEvent::Deleted {
+                    this.pending.clear();
+                    this.done = true;
+                }
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            let mut guard = ready!(this.socket.poll_read_ready(cx))?;
+            let buf = &mut this.buf;
+            let res = guard.try_io(|socket| {
+                match unsafe {
+                    libc::recv(socket.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0)
+                } {
+                    n if n >= 0 => Ok(n as usize),
+                    _ => Err(io::Error::last_os_error()),
+                }
+            });
+            match res {
+                Ok(Ok(n)) => this.handle(n),
+                Ok(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
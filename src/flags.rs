@@ -0,0 +1,88 @@
+use std::fmt;
+
+bitflags::bitflags! {
+    /// Represents the flags of a network interface.
+    ///
+    /// See the [linux manpage](https://man7.org/linux/man-pages/man7/netdevice.7.html) under
+    /// section *SIOCGIFFLAGS, SIOCSIFFLAGS* for an official description.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct InterfaceFlags: u32 {
+        /// Interface is running.
+        const UP = libc::IFF_UP as u32;
+        /// Valid broadcast address set.
+        const BROADCAST = libc::IFF_BROADCAST as u32;
+        /// Internal debugging flag.
+        const DEBUG = libc::IFF_DEBUG as u32;
+        /// Interface is a loopback interface.
+        const LOOPBACK = libc::IFF_LOOPBACK as u32;
+        /// Interface is a point-to-point link.
+        const POINTOPOINT = libc::IFF_POINTOPOINT as u32;
+        /// Avoid use of trailers.
+        const NOTRAILERS = libc::IFF_NOTRAILERS as u32;
+        /// Resources allocated.
+        const RUNNING = libc::IFF_RUNNING as u32;
+        /// No arp protocol, L2 destination address not set.
+        const NOARP = libc::IFF_NOARP as u32;
+        /// Interface is in promiscuous mode.
+        const PROMISC = libc::IFF_PROMISC as u32;
+        /// Receive all multicast packets.
+        const ALLMULTI = libc::IFF_ALLMULTI as u32;
+        /// Master of a load balancing bundle.
+        const MASTER = libc::IFF_MASTER as u32;
+        /// Slave of a load balancing bundle.
+        const SLAVE = libc::IFF_SLAVE as u32;
+        /// Supports multicast.
+        const MULTICAST = libc::IFF_MULTICAST as u32;
+        /// Is able to select media type via ifmap.
+        const PORTSEL = libc::IFF_PORTSEL as u32;
+        /// Auto media selection active.
+        const AUTOMEDIA = libc::IFF_AUTOMEDIA as u32;
+        /// The addresses are lost when the interface goes down.
+        const DYNAMIC = libc::IFF_DYNAMIC as u32;
+        /// Driver signals L1 up (read-only).
+        const LOWER_UP = libc::IFF_LOWER_UP as u32;
+        /// Driver signals dormant (read-only).
+        const DORMANT = libc::IFF_DORMANT as u32;
+        /// Echo sent packets (read-only).
+        const ECHO = libc::IFF_ECHO as u32;
+    }
+}
+
+bitflags::bitflags! {
+    /// Represents the offloads which the kernel may use when passing packets to the device,
+    /// configured through `TUNSETOFFLOAD`.
+    ///
+    /// Offloads require vnet headers to be enabled, since the segmentation and checksum metadata
+    /// of each packet is carried by its [`VirtioNetHdr`](struct.VirtioNetHdr.html).
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct OffloadFlags: u32 {
+        /// Packets may have partial checksums.
+        const CSUM = libc::TUN_F_CSUM;
+        /// TCP segmentation offload over IPv4.
+        const TSO4 = libc::TUN_F_TSO4;
+        /// TCP segmentation offload over IPv6.
+        const TSO6 = libc::TUN_F_TSO6;
+        /// TCP segmentation offload with ECN bits.
+        const TSO_ECN = libc::TUN_F_TSO_ECN;
+        /// UDP fragmentation offload.
+        const UFO = libc::TUN_F_UFO;
+        /// UDP segmentation offload over IPv4.
+        const USO4 = libc::TUN_F_USO4;
+        /// UDP segmentation offload over IPv6.
+        const USO6 = libc::TUN_F_USO6;
+    }
+}
+
+impl fmt::Display for InterfaceFlags {
+    /// Writes the flag names separated by `|`, e.g. `UP | RUNNING | LOWER_UP`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+impl fmt::Display for OffloadFlags {
+    /// Writes the flag names separated by `|`, e.g. `CSUM | TSO4 | TSO6`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
@@ -0,0 +1,681 @@
+//! Userspace segmentation (GSO) and coalescing (GRO) of TCP/UDP packets for devices with vnet
+//! headers.
+//!
+//! When offloads are enabled using [`TunBuilder::offload`](../struct.TunBuilder.html#method.offload),
+//! the kernel passes super-packets of up to 64 KiB to the device. [`segment`] splits them back
+//! into MTU-sized packets with valid headers and checksums. In the opposite direction,
+//! [`Coalescer`] merges consecutive TCP segments of the same flow into super-packets, so that
+//! many small packets can be written to the device at once.
+//!
+//! Packets must start with their IP header, i.e. the device must be a TUN device built without
+//! [`TunBuilder::packet_info`](../struct.TunBuilder.html#method.packet_info).
+
+use crate::VirtioNetHdr;
+use std::io;
+
+const IPV4_HDR_LEN: usize = 20;
+const IPV6_HDR_LEN: usize = 40;
+const TCP_HDR_LEN: usize = 20;
+const UDP_HDR_LEN: usize = 8;
+const MAX_PACKET_LEN: usize = u16::MAX as usize;
+
+const IPPROTO_TCP: u8 = libc::IPPROTO_TCP as u8;
+const IPPROTO_UDP: u8 = libc::IPPROTO_UDP as u8;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+const TCP_CWR: u8 = 0x80;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Adds `data` to the one's complement sum `sum`.
+fn checksum_add(mut sum: u64, data: &[u8]) -> u64 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u64;
+    }
+    if let [byte] = chunks.remainder() {
+        sum += (*byte as u64) << 8;
+    }
+    sum
+}
+
+fn checksum_fold(mut sum: u64) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// Returns the sum of the TCP/UDP pseudo-header of `packet` for a transport segment of `len`
+/// bytes.
+fn pseudo_header_sum(packet: &[u8], proto: u8, len: usize) -> u64 {
+    let addresses = match packet[0] >> 4 {
+        4 => &packet[12..20],
+        _ => &packet[8..40],
+    };
+    checksum_add(0, addresses) + proto as u64 + (len >> 16) as u64 + (len & 0xffff) as u64
+}
+
+fn ipv4_header_checksum(packet: &mut [u8], ip_hdr_len: usize) {
+    write_u16(packet, 10, 0);
+    let csum = !checksum_fold(checksum_add(0, &packet[..ip_hdr_len]));
+    write_u16(packet, 10, csum);
+}
+
+/// Updates the length fields of the IP header after the packet has been resized.
+fn update_ip_len(packet: &mut [u8], ip_hdr_len: usize) {
+    let len = packet.len();
+    if packet[0] >> 4 == 4 {
+        write_u16(packet, 2, len as u16);
+        ipv4_header_checksum(packet, ip_hdr_len);
+    } else {
+        write_u16(packet, 4, (len - IPV6_HDR_LEN) as u16);
+    }
+}
+
+/// Returns the IP version and the length of the IP header of `packet`, excluding IPv6
+/// extension headers.
+fn ip_header(packet: &[u8]) -> io::Result<(u8, usize)> {
+    let ihl = packet.first().map_or(0, |b| (b & 0x0f) as usize * 4);
+    match packet.first().map(|b| b >> 4) {
+        Some(4) if ihl >= IPV4_HDR_LEN && packet.len() >= ihl => Ok((4, ihl)),
+        Some(6) if packet.len() >= IPV6_HDR_LEN => Ok((6, IPV6_HDR_LEN)),
+        _ => Err(invalid("packet does not start with a valid IP header")),
+    }
+}
+
+/// Computes the partial checksum described by `csum_start` and `csum_offset`.
+///
+/// The checksum field is expected to hold the sum of the pseudo-header, as done by the kernel.
+fn complete_checksum(packet: &mut [u8], csum_start: usize, csum_offset: usize) -> io::Result<()> {
+    let field = csum_start + csum_offset;
+    if field + 2 > packet.len() {
+        return Err(invalid("checksum offset is out of bounds"));
+    }
+    let csum = !checksum_fold(checksum_add(0, &packet[csum_start..]));
+    write_u16(packet, field, csum);
+    Ok(())
+}
+
+/// Splits a packet received along with `hdr` into packets whose payload is at most
+/// `hdr.gso_size` bytes long.
+///
+/// The IP and TCP/UDP headers of every segment are rewritten (lengths, IPv4 identification,
+/// TCP sequence number and flags) and their checksums are computed. Packets which are not GSO
+/// packets are returned as-is, except that a pending partial checksum is completed.
+///
+/// Only TCP (`GSO_TCPV4`, `GSO_TCPV6`) and UDP (`GSO_UDP_L4`) segmentation is supported.
+pub fn segment(hdr: &VirtioNetHdr, packet: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+    if !hdr.is_gso() {
+        let mut packet = packet.to_vec();
+        if hdr.flags & VirtioNetHdr::FLAG_NEEDS_CSUM != 0 {
+            complete_checksum(
+                &mut packet,
+                hdr.csum_start as usize,
+                hdr.csum_offset as usize,
+            )?;
+        }
+        return Ok(vec![packet]);
+    }
+
+    let (version, ip_hdr_len) = ip_header(packet)?;
+    let proto = match hdr.gso_type & !VirtioNetHdr::GSO_ECN {
+        VirtioNetHdr::GSO_TCPV4 | VirtioNetHdr::GSO_TCPV6 => IPPROTO_TCP,
+        VirtioNetHdr::GSO_UDP_L4 => IPPROTO_UDP,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unsupported GSO type",
+            ));
+        }
+    };
+
+    let l4 = hdr.csum_start as usize;
+    let l4_min_len = if proto == IPPROTO_TCP {
+        TCP_HDR_LEN
+    } else {
+        UDP_HDR_LEN
+    };
+    if l4 < ip_hdr_len || packet.len() < l4 + l4_min_len {
+        return Err(invalid("transport header is out of bounds"));
+    }
+    let hdr_len = if proto == IPPROTO_TCP {
+        l4 + (packet[l4 + 12] >> 4) as usize * 4
+    } else {
+        l4 + UDP_HDR_LEN
+    };
+    let gso_size = hdr.gso_size as usize;
+    if hdr_len < l4 + l4_min_len || hdr_len > packet.len() || gso_size == 0 {
+        return Err(invalid("invalid GSO header"));
+    }
+
+    let payload = &packet[hdr_len..];
+    let chunks = if payload.is_empty() {
+        vec![payload]
+    } else {
+        payload.chunks(gso_size).collect()
+    };
+    let count = chunks.len();
+    let ipv4_id = read_u16(packet, 4);
+    let seq = if proto == IPPROTO_TCP {
+        read_u32(packet, l4 + 4)
+    } else {
+        0
+    };
+
+    let mut segments = Vec::with_capacity(count);
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let mut seg = Vec::with_capacity(hdr_len + chunk.len());
+        seg.extend_from_slice(&packet[..hdr_len]);
+        seg.extend_from_slice(chunk);
+
+        if version == 4 {
+            write_u16(&mut seg, 4, ipv4_id.wrapping_add(i as u16));
+        }
+        update_ip_len(&mut seg, ip_hdr_len);
+
+        let l4_len = seg.len() - l4;
+        let csum_offset = if proto == IPPROTO_TCP {
+            write_u32(&mut seg, l4 + 4, seq.wrapping_add((i * gso_size) as u32));
+            if i + 1 != count {
+                seg[l4 + 13] &= !(TCP_FIN | TCP_PSH);
+            }
+            if i != 0 {
+                seg[l4 + 13] &= !TCP_CWR;
+            }
+            16
+        } else {
+            write_u16(&mut seg, l4 + 4, l4_len as u16);
+            6
+        };
+
+        write_u16(&mut seg, l4 + csum_offset, 0);
+        let sum = checksum_add(pseudo_header_sum(&seg, proto, l4_len), &seg[l4..]);
+        let csum = match !checksum_fold(sum) {
+            0 if proto == IPPROTO_UDP => 0xffff,
+            csum => csum,
+        };
+        write_u16(&mut seg, l4 + csum_offset, csum);
+        segments.push(seg);
+    }
+    Ok(segments)
+}
+
+/// Describes a TCP packet which may be coalesced.
+struct TcpSegment {
+    version: u8,
+    ip_hdr_len: usize,
+    hdr_len: usize,
+    seq: u32,
+    flags: u8,
+}
+
+impl TcpSegment {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        let (version, ip_hdr_len) = ip_header(packet).ok()?;
+        let (proto, total_len) = match version {
+            // Options and fragments are not coalesced.
+            4 if ip_hdr_len == IPV4_HDR_LEN && read_u16(packet, 6) & 0x3fff == 0 => {
+                (packet[9], read_u16(packet, 2) as usize)
+            }
+            6 => (packet[6], IPV6_HDR_LEN + read_u16(packet, 4) as usize),
+            _ => return None,
+        };
+        if proto != IPPROTO_TCP
+            || total_len != packet.len()
+            || packet.len() < ip_hdr_len + TCP_HDR_LEN
+        {
+            return None;
+        }
+        let hdr_len = ip_hdr_len + (packet[ip_hdr_len + 12] >> 4) as usize * 4;
+        if hdr_len < ip_hdr_len + TCP_HDR_LEN || hdr_len > packet.len() {
+            return None;
+        }
+        Some(Self {
+            version,
+            ip_hdr_len,
+            hdr_len,
+            seq: read_u32(packet, ip_hdr_len + 4),
+            flags: packet[ip_hdr_len + 13],
+        })
+    }
+
+    fn payload_len(&self, packet: &[u8]) -> usize {
+        packet.len() - self.hdr_len
+    }
+
+    /// Returns `true` if only `ACK` (and `PSH`) are set and the segment carries data.
+    fn is_coalescable(&self, packet: &[u8]) -> bool {
+        self.flags & !(TCP_ACK | TCP_PSH) == 0
+            && self.flags & TCP_ACK != 0
+            && self.payload_len(packet) > 0
+    }
+}
+
+/// Represents a packet waiting to be written, possibly made of several coalesced segments.
+struct Pending {
+    buf: Vec<u8>,
+    tcp: Option<TcpSegment>,
+    gso_size: usize,
+    segments: usize,
+    next_seq: u32,
+    closed: bool,
+}
+
+impl Pending {
+    fn new(packet: &[u8]) -> Self {
+        let tcp = TcpSegment::parse(packet).filter(|tcp| tcp.is_coalescable(packet));
+        let gso_size = tcp.as_ref().map_or(0, |tcp| tcp.payload_len(packet));
+        Self {
+            buf: packet.to_vec(),
+            next_seq: tcp
+                .as_ref()
+                .map_or(0, |tcp| tcp.seq.wrapping_add(gso_size as u32)),
+            closed: tcp.as_ref().is_none_or(|tcp| tcp.flags & TCP_PSH != 0),
+            tcp,
+            gso_size,
+            segments: 1,
+        }
+    }
+
+    /// Returns `true` if `packet` belongs to the same flow as this packet.
+    fn same_flow(&self, packet: &[u8], seg: &TcpSegment) -> bool {
+        let Some(tcp) = &self.tcp else {
+            return false;
+        };
+        let addresses = if tcp.version == 4 { 12..20 } else { 8..40 };
+        let ports = tcp.ip_hdr_len..tcp.ip_hdr_len + 4;
+        tcp.version == seg.version
+            && self.buf[addresses.clone()] == packet[addresses]
+            && self.buf[ports.clone()] == packet[ports]
+    }
+
+    /// Appends the payload of `packet` if it directly follows the coalesced segments.
+    fn try_append(&mut self, packet: &[u8], seg: &TcpSegment) -> bool {
+        let Some(tcp) = &self.tcp else {
+            return false;
+        };
+        let l4 = tcp.ip_hdr_len;
+        let payload_len = seg.payload_len(packet);
+        let same_ip_header = if tcp.version == 4 {
+            // TOS, DF flag and TTL.
+            self.buf[1] == packet[1] && self.buf[6] == packet[6] && self.buf[8] == packet[8]
+        } else {
+            // Traffic class, flow label and hop limit.
+            self.buf[..4] == packet[..4] && self.buf[7] == packet[7]
+        };
+        if self.closed
+            || !same_ip_header
+            || seg.hdr_len != tcp.hdr_len
+            || seg.seq != self.next_seq
+            || payload_len > self.gso_size
+            || self.buf.len() + payload_len > MAX_PACKET_LEN
+            || self.buf[l4 + 8..l4 + 12] != packet[l4 + 8..l4 + 12]
+            || self.buf[l4 + TCP_HDR_LEN..tcp.hdr_len] != packet[l4 + TCP_HDR_LEN..tcp.hdr_len]
+        {
+            return false;
+        }
+
+        self.buf.extend_from_slice(&packet[seg.hdr_len..]);
+        // Keep the most recent advertised window.
+        self.buf[l4 + 14..l4 + 16].copy_from_slice(&packet[l4 + 14..l4 + 16]);
+        self.next_seq = seg.seq.wrapping_add(payload_len as u32);
+        self.segments += 1;
+        if seg.flags & TCP_PSH != 0 {
+            self.buf[l4 + 13] |= TCP_PSH;
+            self.closed = true;
+        }
+        if payload_len < self.gso_size {
+            self.closed = true;
+        }
+        true
+    }
+
+    fn finish(mut self) -> (VirtioNetHdr, Vec<u8>) {
+        let tcp = match self.tcp {
+            Some(tcp) if self.segments > 1 => tcp,
+            _ => return (VirtioNetHdr::default(), self.buf),
+        };
+        let l4 = tcp.ip_hdr_len;
+        update_ip_len(&mut self.buf, l4);
+        let l4_len = self.buf.len() - l4;
+        let csum = checksum_fold(pseudo_header_sum(&self.buf, IPPROTO_TCP, l4_len));
+        write_u16(&mut self.buf, l4 + 16, csum);
+        let hdr = VirtioNetHdr {
+            flags: VirtioNetHdr::FLAG_NEEDS_CSUM,
+            gso_type: if tcp.version == 4 {
+                VirtioNetHdr::GSO_TCPV4
+            } else {
+                VirtioNetHdr::GSO_TCPV6
+            },
+            hdr_len: tcp.hdr_len as u16,
+            gso_size: self.gso_size as u16,
+            csum_start: l4 as u16,
+            csum_offset: 16,
+        };
+        (hdr, self.buf)
+    }
+}
+
+/// Coalesces consecutive TCP segments of the same flow into GSO super-packets.
+///
+/// Segments are merged when they directly follow each other in sequence space, have identical
+/// IP and TCP headers (apart from lengths, checksums, sequence number and window) and carry
+/// only the `ACK` and `PSH` flags. All other packets are passed through unchanged.
+///
+/// ```no_run
+/// # async fn run(tun: tokio_tun::Tun, packets: Vec<Vec<u8>>) -> std::io::Result<()> {
+/// use tokio_tun::gso::Coalescer;
+///
+/// let mut coalescer = Coalescer::new();
+/// for packet in &packets {
+///     coalescer.push(packet);
+/// }
+/// for (hdr, packet) in coalescer.drain() {
+///     tun.send_vnet(&hdr, &packet).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Coalescer {
+    pending: Vec<Pending>,
+}
+
+impl Coalescer {
+    /// Creates a new instance of [`Coalescer`](struct.Coalescer.html).
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a packet, merging it into a previously added packet of the same flow if possible.
+    pub fn push(&mut self, packet: &[u8]) {
+        if let Some(seg) = TcpSegment::parse(packet).filter(|seg| seg.is_coalescable(packet)) {
+            let last = self
+                .pending
+                .iter_mut()
+                .rev()
+                .find(|pending| pending.same_flow(packet, &seg));
+            if last.is_some_and(|pending| pending.try_append(packet, &seg)) {
+                return;
+            }
+        }
+        self.pending.push(Pending::new(packet));
+    }
+
+    /// Returns `true` if no packets are pending.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Returns the number of packets which would be written to the device.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Removes all pending packets and returns them along with the vnet header to be used when
+    /// writing them to the device.
+    pub fn drain(&mut self) -> impl Iterator<Item = (VirtioNetHdr, Vec<u8>)> + '_ {
+        self.pending.drain(..).map(Pending::finish)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns `true` if the TCP/UDP checksum of `packet` is valid.
+    fn l4_checksum_valid(packet: &[u8], proto: u8) -> bool {
+        let l4 = ip_header(packet).unwrap().1;
+        let l4_len = packet.len() - l4;
+        checksum_fold(checksum_add(
+            pseudo_header_sum(packet, proto, l4_len),
+            &packet[l4..],
+        )) == 0xffff
+    }
+
+    fn ipv4(proto: u8, id: u16, l4: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0; IPV4_HDR_LEN];
+        packet[0] = 0x45;
+        write_u16(&mut packet, 2, (IPV4_HDR_LEN + l4.len()) as u16);
+        write_u16(&mut packet, 4, id);
+        packet[6] = 0x40;
+        packet[8] = 64;
+        packet[9] = proto;
+        packet[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        packet[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        packet.extend_from_slice(l4);
+        ipv4_header_checksum(&mut packet, IPV4_HDR_LEN);
+        packet
+    }
+
+    fn tcp(id: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut l4 = vec![0; TCP_HDR_LEN];
+        write_u16(&mut l4, 0, 40000);
+        write_u16(&mut l4, 2, 443);
+        write_u32(&mut l4, 4, seq);
+        write_u32(&mut l4, 8, 7);
+        l4[12] = 5 << 4;
+        l4[13] = flags;
+        write_u16(&mut l4, 14, 512);
+        l4.extend_from_slice(payload);
+        let mut packet = ipv4(IPPROTO_TCP, id, &l4);
+        let sum = checksum_add(pseudo_header_sum(&packet, IPPROTO_TCP, l4.len()), &l4);
+        write_u16(&mut packet, IPV4_HDR_LEN + 16, !checksum_fold(sum));
+        packet
+    }
+
+    fn udp(payload: &[u8]) -> Vec<u8> {
+        let mut l4 = vec![0; UDP_HDR_LEN];
+        write_u16(&mut l4, 0, 5353);
+        write_u16(&mut l4, 2, 53);
+        write_u16(&mut l4, 4, (UDP_HDR_LEN + payload.len()) as u16);
+        l4.extend_from_slice(payload);
+        ipv4(IPPROTO_UDP, 1, &l4)
+    }
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    fn tcp_gso(gso_size: u16) -> VirtioNetHdr {
+        VirtioNetHdr {
+            flags: VirtioNetHdr::FLAG_NEEDS_CSUM,
+            gso_type: VirtioNetHdr::GSO_TCPV4,
+            hdr_len: (IPV4_HDR_LEN + TCP_HDR_LEN) as u16,
+            gso_size,
+            csum_start: IPV4_HDR_LEN as u16,
+            csum_offset: 16,
+        }
+    }
+
+    #[test]
+    fn coalesce_segment_round_trip() {
+        let data = payload(3000);
+        let packets = [
+            tcp(100, 1000, TCP_ACK, &data[..1000]),
+            tcp(101, 2000, TCP_ACK, &data[1000..2000]),
+            tcp(102, 3000, TCP_ACK | TCP_PSH, &data[2000..]),
+        ];
+        assert!(packets.iter().all(|p| l4_checksum_valid(p, IPPROTO_TCP)));
+
+        let mut coalescer = Coalescer::new();
+        for packet in &packets {
+            coalescer.push(packet);
+        }
+        assert_eq!(coalescer.len(), 1);
+        let (hdr, packet) = coalescer.drain().next().unwrap();
+        assert_eq!(hdr.gso_type, VirtioNetHdr::GSO_TCPV4);
+        assert_eq!(hdr.gso_size, 1000);
+        assert_eq!(packet.len(), IPV4_HDR_LEN + TCP_HDR_LEN + 3000);
+
+        assert_eq!(segment(&hdr, &packet).unwrap(), packets);
+    }
+
+    #[test]
+    fn coalesce_keeps_other_flows_apart() {
+        let data = payload(100);
+        let mut coalescer = Coalescer::new();
+        coalescer.push(&tcp(1, 0, TCP_ACK, &data));
+        // Not directly following the first segment.
+        coalescer.push(&tcp(2, 1000, TCP_ACK, &data));
+        coalescer.push(&udp(&data));
+        let packets = coalescer.drain().collect::<Vec<_>>();
+        assert_eq!(packets.len(), 3);
+        assert!(packets.iter().all(|(hdr, _)| !hdr.is_gso()));
+    }
+
+    #[test]
+    fn segment_tcp_flags_and_checksums() {
+        let data = payload(2500);
+        let packet = tcp(7, u32::MAX - 100, TCP_ACK | TCP_PSH | TCP_FIN, &data);
+        let segments = segment(&tcp_gso(1000), &packet).unwrap();
+        assert_eq!(segments.len(), 3);
+        for (i, seg) in segments.iter().enumerate() {
+            let l4 = IPV4_HDR_LEN;
+            assert_eq!(read_u16(seg, 2) as usize, seg.len());
+            assert_eq!(read_u16(seg, 4), 7 + i as u16);
+            assert_eq!(
+                read_u32(seg, l4 + 4),
+                (u32::MAX - 100).wrapping_add(i as u32 * 1000)
+            );
+            let last = i == 2;
+            assert_eq!(seg[l4 + 13] & (TCP_PSH | TCP_FIN) != 0, last);
+            assert!(l4_checksum_valid(seg, IPPROTO_TCP));
+            assert_eq!(checksum_fold(checksum_add(0, &seg[..IPV4_HDR_LEN])), 0xffff);
+        }
+        let joined = segments
+            .iter()
+            .flat_map(|seg| &seg[IPV4_HDR_LEN + TCP_HDR_LEN..])
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(joined, data);
+    }
+
+    #[test]
+    fn segment_udp_l4() {
+        let data = payload(2500);
+        let packet = udp(&data);
+        let hdr = VirtioNetHdr {
+            flags: VirtioNetHdr::FLAG_NEEDS_CSUM,
+            gso_type: VirtioNetHdr::GSO_UDP_L4,
+            hdr_len: (IPV4_HDR_LEN + UDP_HDR_LEN) as u16,
+            gso_size: 1000,
+            csum_start: IPV4_HDR_LEN as u16,
+            csum_offset: 6,
+        };
+        let segments = segment(&hdr, &packet).unwrap();
+        assert_eq!(
+            segments.iter().map(Vec::len).collect::<Vec<_>>(),
+            [1028, 1028, 528]
+        );
+        for seg in &segments {
+            assert_eq!(read_u16(seg, 2) as usize, seg.len());
+            assert_eq!(
+                read_u16(seg, IPV4_HDR_LEN + 4) as usize,
+                seg.len() - IPV4_HDR_LEN
+            );
+            assert!(l4_checksum_valid(seg, IPPROTO_UDP));
+        }
+        let joined = segments
+            .iter()
+            .flat_map(|seg| &seg[IPV4_HDR_LEN + UDP_HDR_LEN..])
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(joined, data);
+    }
+
+    #[test]
+    fn segment_completes_checksum_of_non_gso_packet() {
+        let mut packet = udp(&payload(100));
+        let l4_len = packet.len() - IPV4_HDR_LEN;
+        let sum = checksum_fold(pseudo_header_sum(&packet, IPPROTO_UDP, l4_len));
+        write_u16(&mut packet, IPV4_HDR_LEN + 6, sum);
+        let hdr = VirtioNetHdr {
+            flags: VirtioNetHdr::FLAG_NEEDS_CSUM,
+            csum_start: IPV4_HDR_LEN as u16,
+            csum_offset: 6,
+            ..Default::default()
+        };
+        let segments = segment(&hdr, &packet).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert!(l4_checksum_valid(&segments[0], IPPROTO_UDP));
+    }
+
+    #[test]
+    fn segment_rejects_malformed_headers() {
+        let packet = tcp(1, 0, TCP_ACK, &payload(100));
+        let hdr = tcp_gso(10);
+
+        // TCP data offset below 5.
+        let mut short_doff = packet.clone();
+        short_doff[IPV4_HDR_LEN + 12] = 0;
+        let err = segment(&VirtioNetHdr { gso_size: 1, ..hdr }, &short_doff).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // TCP data offset beyond the end of the packet.
+        let mut long_doff = packet[..IPV4_HDR_LEN + TCP_HDR_LEN].to_vec();
+        long_doff[IPV4_HDR_LEN + 12] = 15 << 4;
+        assert!(segment(&hdr, &long_doff).is_err());
+
+        // Truncated transport header.
+        assert!(segment(&hdr, &packet[..IPV4_HDR_LEN + 10]).is_err());
+
+        // Transport header inside the IP header.
+        assert!(
+            segment(
+                &VirtioNetHdr {
+                    csum_start: 10,
+                    ..hdr
+                },
+                &packet
+            )
+            .is_err()
+        );
+
+        // Not an IP packet.
+        assert!(segment(&hdr, &[0x00; 64]).is_err());
+        assert!(segment(&hdr, &[]).is_err());
+
+        // Zero segment size.
+        assert!(segment(&VirtioNetHdr { gso_size: 0, ..hdr }, &packet).is_err());
+
+        // Checksum offset out of bounds.
+        let no_gso = VirtioNetHdr {
+            gso_type: VirtioNetHdr::GSO_NONE,
+            csum_offset: packet.len() as u16,
+            ..hdr
+        };
+        assert!(segment(&no_gso, &packet).is_err());
+
+        let err = segment(
+            &VirtioNetHdr {
+                gso_type: VirtioNetHdr::GSO_UDP,
+                ..hdr
+            },
+            &packet,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
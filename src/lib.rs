@@ -3,14 +3,40 @@ mod linux {
     pub mod address;
     pub mod interface;
     pub mod io;
+    pub mod netlink;
     pub mod params;
     pub mod request;
+    pub mod scm;
 }
 
+pub mod gso;
+
 mod builder;
+mod events;
+mod flags;
+mod mq;
+mod netns;
+mod network;
+mod preflight;
 mod result;
+mod route;
+#[cfg(feature = "runtime")]
+mod runtime;
+mod stats;
 mod tun;
+mod vnet;
 
 pub use self::builder::TunBuilder;
+pub use self::events::{InterfaceEvent, InterfaceEvents};
+pub use self::flags::{InterfaceFlags, OffloadFlags};
+pub use self::mq::MultiQueueTun;
+pub use self::netns::NetNs;
+pub use self::network::IpNetwork;
+pub use self::preflight::{DeviceNode, PreflightReport, preflight};
 pub use self::result::{Error, Result};
-pub use self::tun::Tun;
+pub use self::route::Route;
+#[cfg(feature = "runtime")]
+pub use self::runtime::{QueueWorkers, WorkerStats};
+pub use self::stats::{InterfaceStats, QueueStats};
+pub use self::tun::{Tun, delete_interface};
+pub use self::vnet::VirtioNetHdr;
@@ -1,6 +1,6 @@
 use super::request::sockaddr;
 use std::mem;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 pub trait Ipv4AddrExt {
     fn to_address(&self) -> sockaddr;
@@ -23,3 +23,20 @@ impl Ipv4AddrExt for Ipv4Addr {
         sock.sin_addr.s_addr.to_ne_bytes().into()
     }
 }
+
+pub trait Ipv6AddrExt {
+    fn to_in6_addr(&self) -> libc::in6_addr;
+    fn from_in6_addr(addr: libc::in6_addr) -> Self;
+}
+
+impl Ipv6AddrExt for Ipv6Addr {
+    fn to_in6_addr(&self) -> libc::in6_addr {
+        libc::in6_addr {
+            s6_addr: self.octets(),
+        }
+    }
+
+    fn from_in6_addr(addr: libc::in6_addr) -> Self {
+        addr.s6_addr.into()
+    }
+}
@@ -1,13 +1,30 @@
+use super::netlink::{self, Netlink};
 use super::params::Params;
 use super::request::ifreq;
-use crate::Result;
-use crate::linux::address::Ipv4AddrExt;
-use std::net::Ipv4Addr;
+use crate::linux::address::{Ipv4AddrExt, Ipv6AddrExt};
+use crate::netns::{self, NetNsGuard};
+use crate::{
+    Error, InterfaceFlags, InterfaceStats, IpNetwork, OffloadFlags, Result, Route, VirtioNetHdr,
+};
+use std::ffi::CStr;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::raw::c_char;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::ptr;
+use std::sync::{Arc, Mutex, RwLock};
+
+static TUN: &[u8] = b"/dev/net/tun\0";
 
 nix::ioctl_write_int!(tunsetiff, b'T', 202);
+nix::ioctl_read!(tungetiff, b'T', 210, libc::c_uint);
 nix::ioctl_write_int!(tunsetpersist, b'T', 203);
 nix::ioctl_write_int!(tunsetowner, b'T', 204);
 nix::ioctl_write_int!(tunsetgroup, b'T', 206);
+nix::ioctl_write_int!(tunsetoffload, b'T', 208);
+nix::ioctl_write_ptr!(tunsetvnethdrsz, b'T', 216, libc::c_int);
+nix::ioctl_write_int!(tunsetqueue, b'T', 217);
 
 nix::ioctl_write_ptr_bad!(siocsifmtu, libc::SIOCSIFMTU, ifreq);
 nix::ioctl_write_ptr_bad!(siocsifflags, libc::SIOCSIFFLAGS, ifreq);
@@ -15,6 +32,10 @@ nix::ioctl_write_ptr_bad!(siocsifaddr, libc::SIOCSIFADDR, ifreq);
 nix::ioctl_write_ptr_bad!(siocsifdstaddr, libc::SIOCSIFDSTADDR, ifreq);
 nix::ioctl_write_ptr_bad!(siocsifbrdaddr, libc::SIOCSIFBRDADDR, ifreq);
 nix::ioctl_write_ptr_bad!(siocsifnetmask, libc::SIOCSIFNETMASK, ifreq);
+nix::ioctl_write_ptr_bad!(siocsifhwaddr, libc::SIOCSIFHWADDR, ifreq);
+nix::ioctl_write_ptr_bad!(siocsiftxqlen, libc::SIOCSIFTXQLEN, ifreq);
+nix::ioctl_write_ptr_bad!(siocsifaddr6, libc::SIOCSIFADDR, libc::in6_ifreq);
+nix::ioctl_write_ptr_bad!(siocdifaddr6, libc::SIOCDIFADDR, libc::in6_ifreq);
 
 nix::ioctl_read_bad!(siocgifmtu, libc::SIOCGIFMTU, ifreq);
 nix::ioctl_read_bad!(siocgifflags, libc::SIOCGIFFLAGS, ifreq);
@@ -22,41 +43,177 @@ nix::ioctl_read_bad!(siocgifaddr, libc::SIOCGIFADDR, ifreq);
 nix::ioctl_read_bad!(siocgifdstaddr, libc::SIOCGIFDSTADDR, ifreq);
 nix::ioctl_read_bad!(siocgifbrdaddr, libc::SIOCGIFBRDADDR, ifreq);
 nix::ioctl_read_bad!(siocgifnetmask, libc::SIOCGIFNETMASK, ifreq);
+nix::ioctl_read_bad!(siocgifhwaddr, libc::SIOCGIFHWADDR, ifreq);
+nix::ioctl_read_bad!(siocgifindex, libc::SIOCGIFINDEX, ifreq);
+nix::ioctl_read_bad!(siocgiftxqlen, libc::SIOCGIFTXQLEN, ifreq);
+
+/// Represents the sockets used to configure an interface, which belong to the network namespace
+/// they were created in.
+struct Control {
+    socket: OwnedFd,
+    socket6: OwnedFd,
+    netlink: Option<Arc<Netlink>>,
+    /// Namespace the sockets belong to, if it could be opened.
+    netns: Option<OwnedFd>,
+}
+
+impl Control {
+    fn new(cloexec: bool) -> Result<Self> {
+        Ok(Self {
+            netns: netns::current().ok(),
+            socket: socket(libc::AF_INET, cloexec)?,
+            socket6: socket(libc::AF_INET6, cloexec)?,
+            netlink: Netlink::new(cloexec).ok().map(Arc::new),
+        })
+    }
+}
 
-#[derive(Clone)]
 pub struct Interface {
-    fds: Vec<i32>,
-    socket: i32,
+    /// Replaced when the interface is moved to another network namespace.
+    control: RwLock<Arc<Control>>,
+    /// Routes to remove on drop, `None` unless enabled.
+    routes: Mutex<Option<Vec<Route>>>,
     name: String,
+    flags: i16,
+    cloexec: bool,
+    is_tap: bool,
+    vnet_hdr: bool,
+    /// Whether the queues keep counters in the process.
+    queue_stats: bool,
 }
 
 impl Interface {
-    pub fn new(fds: Vec<i32>, name: &str, mut flags: i16, cloexec: bool) -> Result<Self> {
-        let extra_flags = if cloexec { libc::O_CLOEXEC } else { 0 };
+    /// Attaches the given queues to the interface `name`, creating it if it does not exist.
+    pub fn new(fds: &[BorrowedFd<'_>], name: &str, mut flags: i16, cloexec: bool) -> Result<Self> {
         let mut req = ifreq::new(name);
         if fds.len() > 1 {
             flags |= libc::IFF_MULTI_QUEUE as i16;
         }
         req.ifr_ifru.ifru_flags = flags;
-        for &fd in &fds {
-            unsafe { tunsetiff(fd, &req as *const _ as _) }?;
+        for fd in fds {
+            unsafe { tunsetiff(fd.as_raw_fd(), &req as *const _ as _) }
+                .map_err(|err| Error::ioctl("TUNSETIFF", name, err))?;
+        }
+        Self::with_name(req.name()?, flags, cloexec)
+    }
+
+    /// Creates an instance from the file descriptors of already attached queues, querying the
+    /// name and flags of their interface using `TUNGETIFF`.
+    pub fn from_fds(fds: &[BorrowedFd<'_>]) -> Result<Self> {
+        let mut name: Option<String> = None;
+        let mut flags = 0;
+        for fd in fds {
+            let fd = fd.as_raw_fd();
+            let mut req = ifreq::new("");
+            unsafe { tungetiff(fd, &mut req as *mut _ as _) }
+                .map_err(|err| Error::ioctl("TUNGETIFF", &format!("fd {fd}"), err))?;
+            let req_name = req.name()?;
+            match &name {
+                Some(name) if name != req_name => {
+                    return Err(Error::InvalidDevice {
+                        interface: name.clone(),
+                        reason: format!("another queue belongs to `{req_name}`"),
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    name = Some(req_name.to_owned());
+                    flags = unsafe { req.ifr_ifru.ifru_flags } & ATTACH_FLAGS;
+                }
+            }
         }
+        let name = name.ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no queue"))?;
+        let cloexec = match unsafe { libc::fcntl(fds[0].as_raw_fd(), libc::F_GETFD) } {
+            fd_flags if fd_flags >= 0 => fd_flags & libc::FD_CLOEXEC != 0,
+            _ => return Err(io::Error::last_os_error().into()),
+        };
+        Self::with_name(&name, flags, cloexec)
+    }
+
+    fn with_name(name: &str, flags: i16, cloexec: bool) -> Result<Self> {
         Ok(Interface {
-            fds,
-            socket: unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | extra_flags, 0) },
-            name: req.name().to_owned(),
+            control: RwLock::new(Arc::new(Control::new(cloexec)?)),
+            routes: Mutex::new(None),
+            name: name.to_owned(),
+            flags,
+            cloexec,
+            is_tap: flags & libc::IFF_TAP as i16 != 0,
+            vnet_hdr: flags & libc::IFF_VNET_HDR as i16 != 0,
+            queue_stats: false,
         })
     }
 
-    pub fn init(&self, params: Params) -> Result<()> {
+    /// Configures the interface, using `fd` (any of its queues) for the device-wide `TUNSET*`
+    /// requests.
+    pub fn init(&self, fd: BorrowedFd<'_>, params: Params) -> Result<()> {
+        if self.vnet_hdr {
+            self.vnet_hdr_len(fd, VirtioNetHdr::LEN as _)?;
+        }
+        if let Some(offload) = params.offload {
+            self.offload(fd, offload)?;
+        }
         if let Some(mtu) = params.mtu {
             self.mtu(Some(mtu))?;
         }
+        if let Some(len) = params.txqueuelen {
+            self.txqueuelen(Some(len))?;
+        }
         if let Some(owner) = params.owner {
-            self.owner(owner)?;
+            self.owner(fd, owner)?;
         }
         if let Some(group) = params.group {
-            self.group(group)?;
+            self.group(fd, group)?;
+        }
+        if let Some(mac) = params.mac_address {
+            self.mac_address(Some(mac))?;
+        }
+        self.init_ipv4(&params)?;
+        for &address in &params.addresses {
+            self.add_address(address)?;
+        }
+        if params.persist {
+            self.persist(fd, true)?;
+        }
+        let mut flags = params.interface_flags;
+        if params.up {
+            flags |= InterfaceFlags::UP | InterfaceFlags::RUNNING;
+        }
+        if !flags.is_empty() {
+            self.update_flags(flags, InterfaceFlags::empty())?;
+        }
+        self.remove_routes_on_drop(params.remove_routes);
+        for &route in &params.routes {
+            self.add_route(route)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the primary IPv4 address at once through netlink, or one attribute at a time using
+    /// ioctls.
+    fn init_ipv4(&self, params: &Params) -> Result<()> {
+        if let Some(netlink) = self.netlink() {
+            if params.address.is_none()
+                && params.netmask.is_none()
+                && params.destination.is_none()
+                && params.broadcast.is_none()
+            {
+                return Ok(());
+            }
+            return self.update_ipv4(&netlink, |primary, link_flags| {
+                if let Some(address) = params.address {
+                    set_address(primary, address, link_flags)?;
+                }
+                if let Some(netmask) = params.netmask {
+                    set_netmask(primary, netmask, link_flags)?;
+                }
+                if let Some(destination) = params.destination {
+                    primary.peer = Some(destination.into());
+                }
+                if let Some(broadcast) = params.broadcast {
+                    primary.broadcast = Some(broadcast.into());
+                }
+                Ok(())
+            });
         }
         if let Some(address) = params.address {
             self.address(Some(address))?;
@@ -70,112 +227,1099 @@ impl Interface {
         if let Some(broadcast) = params.broadcast {
             self.broadcast(Some(broadcast))?;
         }
-        if params.persist {
-            self.persist()?;
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn is_multi_queue(&self) -> bool {
+        self.flags & libc::IFF_MULTI_QUEUE as i16 != 0
+    }
+
+    /// Opens a file descriptor attached to interface as an additional queue.
+    ///
+    /// `TUNSETIFF` looks the name up in the namespace the clone device was opened in, so this
+    /// happens in the namespace of the interface, lest another device be created.
+    pub fn open_queue(&self) -> Result<OwnedFd> {
+        let control = self.control();
+        let _guard = match &control.netns {
+            Some(ns) => Some(NetNsGuard::enter(ns.as_fd()).map_err(Error::NetNs)?),
+            None => None,
+        };
+        let fd = open(self.cloexec)?;
+        let mut req = ifreq::new(self.name());
+        req.ifr_ifru.ifru_flags = self.flags;
+        unsafe { tunsetiff(fd.as_raw_fd(), &req as *const _ as _) }
+            .map_err(|err| Error::ioctl("TUNSETIFF", self.name(), err))?;
+        Ok(fd)
+    }
+
+    /// Enables (attaches) or disables (detaches) the queue of the given file descriptor.
+    pub fn set_queue(&self, fd: BorrowedFd<'_>, attach: bool) -> Result<()> {
+        let mut req = ifreq::new(self.name());
+        req.ifr_ifru.ifru_flags = if attach {
+            libc::IFF_ATTACH_QUEUE
+        } else {
+            libc::IFF_DETACH_QUEUE
+        } as _;
+        unsafe { tunsetqueue(fd.as_raw_fd(), &req as *const _ as _) }
+            .map_err(|err| Error::ioctl("TUNSETQUEUE", self.name(), err))?;
+        Ok(())
+    }
+
+    pub fn has_vnet_hdr(&self) -> bool {
+        self.vnet_hdr
+    }
+
+    pub fn has_queue_stats(&self) -> bool {
+        self.queue_stats
+    }
+
+    pub fn set_queue_stats(&mut self, enabled: bool) {
+        self.queue_stats = enabled;
+    }
+
+    pub fn mtu(&self, mtu: Option<i32>) -> Result<i32> {
+        let Some(netlink) = self.netlink() else {
+            return self.ioctl_mtu(mtu);
+        };
+        if let Some(mtu) = mtu {
+            self.set_link(&netlink, |msg| msg.u32_attr(netlink::IFLA_MTU, mtu as u32))?;
+            return Ok(mtu);
         }
-        if params.up {
-            self.flags(Some(libc::IFF_UP as i16 | libc::IFF_RUNNING as i16))?;
+        Ok(self.link(&netlink)?.mtu.unwrap_or_default() as i32)
+    }
+
+    pub fn txqueuelen(&self, len: Option<u32>) -> Result<u32> {
+        let Some(netlink) = self.netlink() else {
+            return self.ioctl_txqueuelen(len);
+        };
+        if let Some(len) = len {
+            self.set_link(&netlink, |msg| msg.u32_attr(netlink::IFLA_TXQLEN, len))?;
+            return Ok(len);
+        }
+        Ok(self.link(&netlink)?.txqueuelen.unwrap_or_default())
+    }
+
+    pub fn stats(&self) -> Result<InterfaceStats> {
+        match self.netlink() {
+            Some(netlink) => match self.link(&netlink)?.stats {
+                Some(stats) => Ok(stats),
+                None => self.sysfs_stats(),
+            },
+            None => self.sysfs_stats(),
+        }
+    }
+
+    pub fn netmask(&self, netmask: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+        match (self.netlink(), netmask) {
+            (Some(netlink), Some(netmask)) => {
+                self.update_ipv4(&netlink, |primary, link_flags| {
+                    set_netmask(primary, netmask, link_flags)
+                })?;
+                Ok(netmask)
+            }
+            (Some(netlink), None) => Ok(ipv4(self.ipv4(&netlink)?.local.netmask())),
+            (None, netmask) => self.ioctl_netmask(netmask),
+        }
+    }
+
+    pub fn address(&self, address: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+        match (self.netlink(), address) {
+            (Some(netlink), Some(address)) => {
+                self.update_ipv4(&netlink, |primary, link_flags| {
+                    set_address(primary, address, link_flags)
+                })?;
+                Ok(address)
+            }
+            (Some(netlink), None) => Ok(ipv4(self.ipv4(&netlink)?.local.address())),
+            (None, address) => self.ioctl_address(address),
+        }
+    }
+
+    pub fn destination(&self, dst: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+        match (self.netlink(), dst) {
+            (Some(netlink), Some(dst)) => {
+                self.update_ipv4(&netlink, |primary, _| {
+                    primary.peer = Some(dst.into());
+                    Ok(())
+                })?;
+                Ok(dst)
+            }
+            (Some(netlink), None) => {
+                let primary = self.ipv4(&netlink)?;
+                Ok(ipv4(primary.peer.unwrap_or(primary.local.address())))
+            }
+            (None, dst) => self.ioctl_destination(dst),
+        }
+    }
+
+    pub fn broadcast(&self, broadcast: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+        match (self.netlink(), broadcast) {
+            (Some(netlink), Some(broadcast)) => {
+                self.update_ipv4(&netlink, |primary, _| {
+                    primary.broadcast = Some(broadcast.into());
+                    Ok(())
+                })?;
+                Ok(broadcast)
+            }
+            (Some(netlink), None) => Ok(self
+                .ipv4(&netlink)?
+                .broadcast
+                .map_or(Ipv4Addr::UNSPECIFIED, ipv4)),
+            (None, broadcast) => self.ioctl_broadcast(broadcast),
+        }
+    }
+
+    pub fn mac_address(&self, mac: Option<[u8; 6]>) -> Result<[u8; 6]> {
+        if !self.is_tap {
+            return Err(Error::Unsupported {
+                op: if mac.is_some() {
+                    "SIOCSIFHWADDR"
+                } else {
+                    "SIOCGIFHWADDR"
+                },
+                interface: self.name().to_owned(),
+                reason: "MAC addresses are only supported by TAP devices",
+            });
+        }
+        let Some(netlink) = self.netlink() else {
+            return self.ioctl_mac_address(mac);
+        };
+        if let Some(mac) = mac {
+            self.set_link(&netlink, |msg| msg.attr(netlink::IFLA_ADDRESS, &mac))?;
+            return Ok(mac);
+        }
+        Ok(self.link(&netlink)?.mac_address.unwrap_or_default())
+    }
+
+    pub fn index(&self) -> Result<i32> {
+        if let Some(netlink) = self.netlink() {
+            return Ok(self.link(&netlink)?.index as i32);
+        }
+        let mut req = ifreq::new(self.name());
+        unsafe { siocgifindex(self.control().socket.as_raw_fd(), &mut req) }
+            .map_err(|err| Error::ioctl("SIOCGIFINDEX", self.name(), err))?;
+        Ok(unsafe { req.ifr_ifru.ifru_ivalue })
+    }
+
+    pub fn addresses(&self) -> Result<Vec<IpNetwork>> {
+        match self.netlink() {
+            Some(netlink) => Ok(self
+                .netlink_addresses(&netlink)?
+                .into_iter()
+                .map(|address| address.local)
+                .collect()),
+            None => self.ifaddrs(),
+        }
+    }
+
+    pub fn add_address(&self, address: IpNetwork) -> Result<()> {
+        let Some(netlink) = self.netlink() else {
+            return match address.address() {
+                IpAddr::V4(addr) => self.ioctl_add_address4(addr, address.prefix_len()),
+                IpAddr::V6(addr) => self.ioctl_address6(addr, address.prefix_len(), false),
+            };
+        };
+        let address = netlink::Address::new(self.index()? as _, address);
+        self.add_netlink_address(&netlink, &address)
+    }
+
+    pub fn remove_address(&self, address: IpNetwork) -> Result<()> {
+        let Some(netlink) = self.netlink() else {
+            return match address.address() {
+                IpAddr::V4(addr) => self.ioctl_remove_address4(addr, address.prefix_len()),
+                IpAddr::V6(addr) => self.ioctl_address6(addr, address.prefix_len(), true),
+            };
+        };
+        let address = netlink::Address::new(self.index()? as _, address);
+        self.remove_netlink_address(&netlink, &address)
+    }
+
+    /// Returns the flags of interface including the read-only ones (e.g. `LOWER_UP`) which are
+    /// only reported through netlink.
+    pub fn flags(&self) -> Result<InterfaceFlags> {
+        if let Some(netlink) = self.netlink() {
+            return Ok(InterfaceFlags::from_bits_retain(self.link(&netlink)?.flags));
+        }
+        let mut req = ifreq::new(self.name());
+        unsafe { siocgifflags(self.control().socket.as_raw_fd(), &mut req) }
+            .map_err(|err| Error::ioctl("SIOCGIFFLAGS", self.name(), err))?;
+        Ok(InterfaceFlags::from_bits_retain(
+            unsafe { req.ifr_ifru.ifru_flags } as u16 as u32,
+        ))
+    }
+
+    /// Sets the `set` flags and clears the `clear` flags, returning the resulting flags.
+    pub fn update_flags(
+        &self,
+        set: InterfaceFlags,
+        clear: InterfaceFlags,
+    ) -> Result<InterfaceFlags> {
+        if let Some(netlink) = self.netlink() {
+            let msg = netlink::link_message(
+                libc::RTM_NEWLINK,
+                self.name(),
+                set.bits(),
+                (set | clear).bits(),
+            );
+            netlink
+                .request(msg)
+                .map_err(|err| Error::netlink("RTM_NEWLINK", self.name(), err))?;
+            return self.flags();
+        }
+        let flags = (self.flags()? | set) - clear;
+        let mut req = ifreq::new(self.name());
+        req.ifr_ifru.ifru_flags = flags.bits() as u16 as i16;
+        unsafe { siocsifflags(self.control().socket.as_raw_fd(), &req) }
+            .map_err(|err| Error::ioctl("SIOCSIFFLAGS", self.name(), err))?;
+        Ok(flags)
+    }
+
+    /// Returns the routes through the interface in every table but the local one.
+    pub fn routes(&self) -> Result<Vec<Route>> {
+        let netlink = self.require_netlink("RTM_GETROUTE")?;
+        let index = self.index()? as u32;
+        let header = netlink::rtmsg {
+            rtm_family: libc::AF_UNSPEC as _,
+            rtm_dst_len: 0,
+            rtm_src_len: 0,
+            rtm_tos: 0,
+            rtm_table: 0,
+            rtm_protocol: 0,
+            rtm_scope: 0,
+            rtm_type: 0,
+            rtm_flags: 0,
+        };
+        let messages = netlink
+            .dump(netlink::Message::new(libc::RTM_GETROUTE, 0, header))
+            .map_err(|err| Error::netlink("RTM_GETROUTE", self.name(), err))?;
+        Ok(messages
+            .iter()
+            .filter(|(ty, _)| *ty == libc::RTM_NEWROUTE)
+            .filter_map(|(_, payload)| netlink::parse_route(payload))
+            .filter(|(idx, _)| *idx == index)
+            .map(|(_, route)| route)
+            .collect())
+    }
+
+    pub fn add_route(&self, route: Route) -> Result<()> {
+        let netlink = self.require_netlink("RTM_NEWROUTE")?;
+        let flags = libc::NLM_F_CREATE | libc::NLM_F_EXCL;
+        let msg = netlink::route_message(libc::RTM_NEWROUTE, flags, self.index()? as _, &route);
+        netlink
+            .request(msg)
+            .map_err(|err| Error::netlink("RTM_NEWROUTE", self.name(), err))?;
+        if let Some(routes) = self
+            .routes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_mut()
+        {
+            routes.push(route);
         }
         Ok(())
     }
 
-    pub fn files(&self) -> &[i32] {
-        &self.fds
+    pub fn remove_route(&self, route: Route) -> Result<()> {
+        let netlink = self.require_netlink("RTM_DELROUTE")?;
+        let msg = netlink::route_message(libc::RTM_DELROUTE, 0, self.index()? as _, &route);
+        netlink
+            .request(msg)
+            .map_err(|err| Error::netlink("RTM_DELROUTE", self.name(), err))?;
+        if let Some(routes) = self
+            .routes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_mut()
+        {
+            routes.retain(|added| *added != route);
+        }
+        Ok(())
     }
 
-    pub fn name(&self) -> &str {
-        self.name.as_str()
+    /// Moves the interface to the network namespace `ns`, along with the sockets used to
+    /// configure it. The kernel removes its addresses and routes.
+    pub fn move_to_netns(&self, ns: BorrowedFd<'_>) -> Result<()> {
+        let netlink = self.require_netlink("RTM_NEWLINK")?;
+        let control = {
+            let _guard = NetNsGuard::enter(ns).map_err(Error::NetNs)?;
+            Control::new(self.cloexec)?
+        };
+        let msg = netlink::link_message(libc::RTM_NEWLINK, self.name(), 0, 0)
+            .u32_attr(netlink::IFLA_NET_NS_FD, ns.as_raw_fd() as u32);
+        netlink
+            .request(msg)
+            .map_err(|err| Error::netlink("RTM_NEWLINK", self.name(), err))?;
+        *self.control.write().unwrap_or_else(|err| err.into_inner()) = Arc::new(control);
+        if let Some(routes) = self
+            .routes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_mut()
+        {
+            routes.clear();
+        }
+        Ok(())
     }
 
-    pub fn mtu(&self, mtu: Option<i32>) -> Result<i32> {
+    /// Opens a non-blocking netlink socket notified of the changes of links and addresses in the
+    /// namespace of the interface, returning it along with the current attributes of the link.
+    pub fn subscribe(&self) -> Result<(OwnedFd, netlink::Link)> {
+        let control = self.control();
+        let socket = {
+            let _guard = match &control.netns {
+                Some(ns) => Some(NetNsGuard::enter(ns.as_fd()).map_err(Error::NetNs)?),
+                None => None,
+            };
+            netlink::subscribe(self.cloexec)
+        };
+        let socket = socket.map_err(|err| Error::netlink("socket", self.name(), err))?;
+        let netlink = self.require_netlink("RTM_GETLINK")?;
+        Ok((socket, self.link(&netlink)?))
+    }
+
+    /// Enables or disables the removal of the routes added through this instance when it is
+    /// dropped. Disabling it forgets the routes added so far.
+    pub fn remove_routes_on_drop(&self, remove: bool) {
+        let mut routes = self.routes.lock().unwrap_or_else(|err| err.into_inner());
+        match (remove, routes.as_ref()) {
+            (true, None) => *routes = Some(Vec::new()),
+            (false, _) => *routes = None,
+            (true, Some(_)) => {}
+        }
+    }
+
+    pub fn owner(&self, fd: BorrowedFd<'_>, owner: i32) -> Result<()> {
+        unsafe { tunsetowner(fd.as_raw_fd(), owner as _) }
+            .map_err(|err| Error::ioctl("TUNSETOWNER", self.name(), err))?;
+        Ok(())
+    }
+
+    pub fn group(&self, fd: BorrowedFd<'_>, group: i32) -> Result<()> {
+        unsafe { tunsetgroup(fd.as_raw_fd(), group as _) }
+            .map_err(|err| Error::ioctl("TUNSETGROUP", self.name(), err))?;
+        Ok(())
+    }
+
+    pub fn vnet_hdr_len(&self, fd: BorrowedFd<'_>, len: i32) -> Result<()> {
+        unsafe { tunsetvnethdrsz(fd.as_raw_fd(), &len) }
+            .map_err(|err| Error::ioctl("TUNSETVNETHDRSZ", self.name(), err))?;
+        Ok(())
+    }
+
+    pub fn offload(&self, fd: BorrowedFd<'_>, offload: OffloadFlags) -> Result<()> {
+        if !self.vnet_hdr {
+            return Err(Error::Unsupported {
+                op: "TUNSETOFFLOAD",
+                interface: self.name().to_owned(),
+                reason: "offloads require vnet headers to be enabled",
+            });
+        }
+        unsafe { tunsetoffload(fd.as_raw_fd(), offload.bits() as _) }
+            .map_err(|err| Error::ioctl("TUNSETOFFLOAD", self.name(), err))?;
+        Ok(())
+    }
+
+    pub fn persist(&self, fd: BorrowedFd<'_>, persist: bool) -> Result<()> {
+        unsafe { tunsetpersist(fd.as_raw_fd(), persist as _) }
+            .map_err(|err| Error::ioctl("TUNSETPERSIST", self.name(), err))?;
+        Ok(())
+    }
+}
+
+impl Drop for Interface {
+    fn drop(&mut self) {
+        let routes = self
+            .routes
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+        for route in routes.into_iter().flatten() {
+            let _ = self.remove_route(route);
+        }
+    }
+}
+
+/// Netlink requests.
+impl Interface {
+    fn control(&self) -> Arc<Control> {
+        self.control
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    fn netlink(&self) -> Option<Arc<Netlink>> {
+        self.control().netlink.clone()
+    }
+
+    fn require_netlink(&self, op: &'static str) -> Result<Arc<Netlink>> {
+        self.netlink().ok_or_else(|| Error::Unsupported {
+            op,
+            interface: self.name().to_owned(),
+            reason: "no netlink socket could be opened",
+        })
+    }
+
+    /// Returns the attributes of the link, looked up by name.
+    fn link(&self, netlink: &Netlink) -> Result<netlink::Link> {
+        netlink
+            .get(netlink::link_message(libc::RTM_GETLINK, self.name(), 0, 0))
+            .and_then(|reply| {
+                netlink::parse_link(&reply).ok_or_else(|| io::Error::from(ErrorKind::InvalidData))
+            })
+            .map_err(|err| Error::netlink("RTM_GETLINK", self.name(), err))
+    }
+
+    /// Changes the attributes of the link added by `attrs`.
+    fn set_link(
+        &self,
+        netlink: &Netlink,
+        attrs: impl FnOnce(netlink::Message) -> netlink::Message,
+    ) -> Result<()> {
+        let msg = netlink::link_message(libc::RTM_NEWLINK, self.name(), 0, 0);
+        netlink
+            .request(attrs(msg))
+            .map_err(|err| Error::netlink("RTM_NEWLINK", self.name(), err))
+    }
+
+    fn netlink_addresses(&self, netlink: &Netlink) -> Result<Vec<netlink::Address>> {
+        let index = self.index()? as u32;
+        let header = netlink::ifaddrmsg {
+            ifa_family: libc::AF_UNSPEC as _,
+            ifa_prefixlen: 0,
+            ifa_flags: 0,
+            ifa_scope: 0,
+            ifa_index: index,
+        };
+        let messages = netlink
+            .dump(netlink::Message::new(libc::RTM_GETADDR, 0, header))
+            .map_err(|err| Error::netlink("RTM_GETADDR", self.name(), err))?;
+        Ok(messages
+            .iter()
+            .filter(|(ty, _)| *ty == libc::RTM_NEWADDR)
+            .filter_map(|(_, payload)| netlink::parse_address(payload))
+            .filter(|address| address.index == index)
+            .collect())
+    }
+
+    fn add_netlink_address(&self, netlink: &Netlink, address: &netlink::Address) -> Result<()> {
+        let flags = libc::NLM_F_CREATE | libc::NLM_F_EXCL;
+        netlink
+            .request(netlink::address_message(libc::RTM_NEWADDR, flags, address))
+            .map_err(|err| Error::netlink("RTM_NEWADDR", self.name(), err))
+    }
+
+    fn remove_netlink_address(&self, netlink: &Netlink, address: &netlink::Address) -> Result<()> {
+        netlink
+            .request(netlink::address_message(libc::RTM_DELADDR, 0, address))
+            .map_err(|err| Error::netlink("RTM_DELADDR", self.name(), err))
+    }
+
+    /// Returns the primary (i.e. first) IPv4 address, which is the one the `SIOCGIF*ADDR`
+    /// ioctls report.
+    fn ipv4(&self, netlink: &Netlink) -> Result<netlink::Address> {
+        self.netlink_addresses(netlink)?
+            .into_iter()
+            .find(|address| address.local.is_ipv4())
+            .ok_or_else(|| {
+                let err = io::Error::from_raw_os_error(libc::EADDRNOTAVAIL);
+                Error::netlink("RTM_GETADDR", self.name(), err)
+            })
+    }
+
+    /// Replaces the primary IPv4 address by a copy modified by `update`, as the `SIOCSIF*ADDR`
+    /// ioctls do. A new address is added if there is none. `update` also receives the flags of
+    /// the link, from which the kernel derives the defaults of the address.
+    fn update_ipv4(
+        &self,
+        netlink: &Netlink,
+        update: impl FnOnce(&mut netlink::Address, u32) -> Result<()>,
+    ) -> Result<()> {
+        let link = self.link(netlink)?;
+        let existing = match self.ipv4(netlink) {
+            Ok(address) => Some(address),
+            Err(Error::Netlink { source, .. })
+                if source.raw_os_error() == Some(libc::EADDRNOTAVAIL) =>
+            {
+                None
+            }
+            Err(err) => return Err(err),
+        };
+        let mut address = existing
+            .unwrap_or_else(|| netlink::Address::new(link.index, Ipv4Addr::UNSPECIFIED.into()));
+        update(&mut address, link.flags)?;
+        if Some(address) == existing {
+            return Ok(());
+        }
+        if address.local.address().is_unspecified() {
+            let err = io::Error::from_raw_os_error(libc::EADDRNOTAVAIL);
+            return Err(Error::netlink("RTM_NEWADDR", self.name(), err));
+        }
+        let Some(existing) = existing else {
+            return self.add_netlink_address(netlink, &address);
+        };
+        // The kernel cannot change the peer or broadcast address of an existing address, so it
+        // is replaced. Restore it if the new one is rejected, rather than leaving none.
+        self.remove_netlink_address(netlink, &existing)?;
+        self.add_netlink_address(netlink, &address)
+            .inspect_err(|_| {
+                let _ = self.add_netlink_address(netlink, &existing);
+            })
+    }
+}
+
+/// Legacy `SIOC*` ioctls, used if no netlink socket could be opened.
+impl Interface {
+    fn ioctl_mtu(&self, mtu: Option<i32>) -> Result<i32> {
         let mut req = ifreq::new(self.name());
         if let Some(mtu) = mtu {
             req.ifr_ifru.ifru_mtu = mtu;
-            unsafe { siocsifmtu(self.socket, &req) }?;
+            unsafe { siocsifmtu(self.control().socket.as_raw_fd(), &req) }
+                .map_err(|err| Error::ioctl("SIOCSIFMTU", self.name(), err))?;
         } else {
-            unsafe { siocgifmtu(self.socket, &mut req) }?;
+            unsafe { siocgifmtu(self.control().socket.as_raw_fd(), &mut req) }
+                .map_err(|err| Error::ioctl("SIOCGIFMTU", self.name(), err))?;
         }
         Ok(unsafe { req.ifr_ifru.ifru_mtu })
     }
 
-    pub fn netmask(&self, netmask: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+    fn ioctl_txqueuelen(&self, len: Option<u32>) -> Result<u32> {
+        let mut req = ifreq::new(self.name());
+        if let Some(len) = len {
+            req.ifr_ifru.ifru_ivalue = len as i32;
+            unsafe { siocsiftxqlen(self.control().socket.as_raw_fd(), &req) }
+                .map_err(|err| Error::ioctl("SIOCSIFTXQLEN", self.name(), err))?;
+        } else {
+            unsafe { siocgiftxqlen(self.control().socket.as_raw_fd(), &mut req) }
+                .map_err(|err| Error::ioctl("SIOCGIFTXQLEN", self.name(), err))?;
+        }
+        Ok(unsafe { req.ifr_ifru.ifru_ivalue } as u32)
+    }
+
+    /// Reads the statistics from `/sys/class/net/<name>/statistics`, which shows the devices of
+    /// the namespace sysfs was mounted in.
+    fn sysfs_stats(&self) -> Result<InterfaceStats> {
+        let mut values = [0; 10];
+        for (value, field) in values.iter_mut().zip(InterfaceStats::FIELDS) {
+            let path = format!("/sys/class/net/{}/statistics/{field}", self.name());
+            *value =
+                fs::read_to_string(&path)?
+                    .trim()
+                    .parse()
+                    .map_err(|_| Error::InvalidDevice {
+                        interface: self.name().to_owned(),
+                        reason: format!("invalid statistics in `{path}`"),
+                    })?;
+        }
+        Ok(InterfaceStats::from_values(values))
+    }
+
+    fn ioctl_netmask(&self, netmask: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
         let mut req = ifreq::new(self.name());
         if let Some(netmask) = netmask {
             req.ifr_ifru.ifru_netmask = netmask.to_address();
-            unsafe { siocsifnetmask(self.socket, &req) }?;
+            unsafe { siocsifnetmask(self.control().socket.as_raw_fd(), &req) }
+                .map_err(|err| Error::ioctl("SIOCSIFNETMASK", self.name(), err))?;
             return Ok(netmask);
         }
-        unsafe { siocgifnetmask(self.socket, &mut req) }?;
+        unsafe { siocgifnetmask(self.control().socket.as_raw_fd(), &mut req) }
+            .map_err(|err| Error::ioctl("SIOCGIFNETMASK", self.name(), err))?;
         Ok(unsafe { Ipv4Addr::from_address(req.ifr_ifru.ifru_netmask) })
     }
 
-    pub fn address(&self, address: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+    fn ioctl_address(&self, address: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
         let mut req = ifreq::new(self.name());
         if let Some(address) = address {
             req.ifr_ifru.ifru_addr = address.to_address();
-            unsafe { siocsifaddr(self.socket, &req) }?;
+            unsafe { siocsifaddr(self.control().socket.as_raw_fd(), &req) }
+                .map_err(|err| Error::ioctl("SIOCSIFADDR", self.name(), err))?;
             return Ok(address);
         }
-        unsafe { siocgifaddr(self.socket, &mut req) }?;
+        unsafe { siocgifaddr(self.control().socket.as_raw_fd(), &mut req) }
+            .map_err(|err| Error::ioctl("SIOCGIFADDR", self.name(), err))?;
         Ok(unsafe { Ipv4Addr::from_address(req.ifr_ifru.ifru_addr) })
     }
 
-    pub fn destination(&self, dst: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+    fn ioctl_destination(&self, dst: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
         let mut req = ifreq::new(self.name());
         if let Some(dst) = dst {
             req.ifr_ifru.ifru_dstaddr = dst.to_address();
-            unsafe { siocsifdstaddr(self.socket, &req) }?;
+            unsafe { siocsifdstaddr(self.control().socket.as_raw_fd(), &req) }
+                .map_err(|err| Error::ioctl("SIOCSIFDSTADDR", self.name(), err))?;
             return Ok(dst);
         }
-        unsafe { siocgifdstaddr(self.socket, &mut req) }?;
+        unsafe { siocgifdstaddr(self.control().socket.as_raw_fd(), &mut req) }
+            .map_err(|err| Error::ioctl("SIOCGIFDSTADDR", self.name(), err))?;
         Ok(unsafe { Ipv4Addr::from_address(req.ifr_ifru.ifru_dstaddr) })
     }
 
-    pub fn broadcast(&self, broadcast: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+    fn ioctl_broadcast(&self, broadcast: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
         let mut req = ifreq::new(self.name());
         if let Some(broadcast) = broadcast {
             req.ifr_ifru.ifru_broadaddr = broadcast.to_address();
-            unsafe { siocsifbrdaddr(self.socket, &req) }?;
+            unsafe { siocsifbrdaddr(self.control().socket.as_raw_fd(), &req) }
+                .map_err(|err| Error::ioctl("SIOCSIFBRDADDR", self.name(), err))?;
             return Ok(broadcast);
         }
-        unsafe { siocgifbrdaddr(self.socket, &mut req) }?;
+        unsafe { siocgifbrdaddr(self.control().socket.as_raw_fd(), &mut req) }
+            .map_err(|err| Error::ioctl("SIOCGIFBRDADDR", self.name(), err))?;
         Ok(unsafe { Ipv4Addr::from_address(req.ifr_ifru.ifru_broadaddr) })
     }
 
-    pub fn flags(&self, flags: Option<i16>) -> Result<i16> {
+    fn ioctl_mac_address(&self, mac: Option<[u8; 6]>) -> Result<[u8; 6]> {
         let mut req = ifreq::new(self.name());
-        unsafe { siocgifflags(self.socket, &mut req) }?;
-        if let Some(flags) = flags {
-            unsafe { req.ifr_ifru.ifru_flags |= flags };
-            unsafe { siocsifflags(self.socket, &req) }?;
+        if let Some(mac) = mac {
+            req.ifr_ifru.ifru_hwaddr.sa_family = libc::ARPHRD_ETHER;
+            for (dst, src) in unsafe { req.ifr_ifru.ifru_hwaddr.sa_data.iter_mut() }.zip(mac) {
+                *dst = src as _;
+            }
+            unsafe { siocsifhwaddr(self.control().socket.as_raw_fd(), &req) }
+                .map_err(|err| Error::ioctl("SIOCSIFHWADDR", self.name(), err))?;
+            return Ok(mac);
+        }
+        unsafe { siocgifhwaddr(self.control().socket.as_raw_fd(), &mut req) }
+            .map_err(|err| Error::ioctl("SIOCGIFHWADDR", self.name(), err))?;
+        let mut mac = [0u8; 6];
+        for (dst, src) in mac
+            .iter_mut()
+            .zip(unsafe { req.ifr_ifru.ifru_hwaddr.sa_data })
+        {
+            *dst = src as _;
         }
-        Ok(unsafe { req.ifr_ifru.ifru_flags })
+        Ok(mac)
     }
 
-    pub fn owner(&self, owner: i32) -> Result<()> {
-        for fd in self.fds.iter() {
-            unsafe { tunsetowner(*fd, owner as _) }?;
+    /// Sets the primary IPv4 address and its netmask, the ioctls being unable to manage more
+    /// than one IPv4 address.
+    fn ioctl_add_address4(&self, address: Ipv4Addr, prefix_len: u8) -> Result<()> {
+        let netmask = IpNetwork::new(address, prefix_len)?.netmask();
+        match self.ioctl_address(None) {
+            Err(Error::Ioctl { source, .. })
+                if source.raw_os_error() == Some(libc::EADDRNOTAVAIL) => {}
+            Ok(_) => return Err(self.several_ipv4_addresses("SIOCSIFADDR")),
+            Err(err) => return Err(err),
         }
+        self.ioctl_address(Some(address))?;
+        self.ioctl_netmask(Some(ipv4(netmask)))?;
         Ok(())
     }
 
-    pub fn group(&self, group: i32) -> Result<()> {
-        for fd in self.fds.iter() {
-            unsafe { tunsetgroup(*fd, group as _) }?;
+    /// Removes the primary IPv4 address, which `SIOCSIFADDR` does when given `0.0.0.0`.
+    fn ioctl_remove_address4(&self, address: Ipv4Addr, prefix_len: u8) -> Result<()> {
+        let primary = self.ioctl_address(None)?;
+        let netmask = self.ioctl_netmask(None)?;
+        if primary != address || u32::from(netmask).leading_ones() != prefix_len as u32 {
+            return Err(self.several_ipv4_addresses("SIOCSIFADDR"));
         }
+        self.ioctl_address(Some(Ipv4Addr::UNSPECIFIED))?;
         Ok(())
     }
 
-    pub fn persist(&self) -> Result<()> {
-        for fd in self.fds.iter() {
-            unsafe { tunsetpersist(*fd, 1) }?;
+    fn several_ipv4_addresses(&self, op: &'static str) -> Error {
+        Error::Unsupported {
+            op,
+            interface: self.name().to_owned(),
+            reason: "managing several IPv4 addresses requires netlink",
         }
+    }
+
+    /// Adds or removes an IPv6 address using `in6_ifreq`.
+    fn ioctl_address6(&self, address: Ipv6Addr, prefix_len: u8, remove: bool) -> Result<()> {
+        let req = libc::in6_ifreq {
+            ifr6_addr: address.to_in6_addr(),
+            ifr6_prefixlen: prefix_len as _,
+            ifr6_ifindex: self.index()?,
+        };
+        let control = self.control();
+        let socket = control.socket6.as_raw_fd();
+        let (op, res) = if remove {
+            ("SIOCDIFADDR", unsafe { siocdifaddr6(socket, &req) })
+        } else {
+            ("SIOCSIFADDR", unsafe { siocsifaddr6(socket, &req) })
+        };
+        res.map_err(|err| Error::ioctl(op, self.name(), err))?;
         Ok(())
     }
+
+    /// Returns the addresses of the interface using `getifaddrs`.
+    fn ifaddrs(&self) -> Result<Vec<IpNetwork>> {
+        let mut ifap = ptr::null_mut();
+        if unsafe { libc::getifaddrs(&mut ifap) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let mut addresses = Vec::new();
+        let mut cursor = ifap;
+        while let Some(ifa) = unsafe { cursor.as_ref() } {
+            cursor = ifa.ifa_next;
+            if ifa.ifa_addr.is_null()
+                || unsafe { CStr::from_ptr(ifa.ifa_name) }.to_bytes() != self.name.as_bytes()
+            {
+                continue;
+            }
+            let network = match unsafe { (*ifa.ifa_addr).sa_family } as i32 {
+                libc::AF_INET => {
+                    let addr = unsafe { *ifa.ifa_addr.cast::<libc::sockaddr_in>() };
+                    let prefix_len = if ifa.ifa_netmask.is_null() {
+                        32
+                    } else {
+                        let mask = unsafe { *ifa.ifa_netmask.cast::<libc::sockaddr_in>() };
+                        u32::from_be(mask.sin_addr.s_addr).leading_ones() as u8
+                    };
+                    IpNetwork::new(
+                        Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                        prefix_len,
+                    )
+                }
+                libc::AF_INET6 => {
+                    let addr = unsafe { *ifa.ifa_addr.cast::<libc::sockaddr_in6>() };
+                    let prefix_len = if ifa.ifa_netmask.is_null() {
+                        128
+                    } else {
+                        let mask = unsafe { *ifa.ifa_netmask.cast::<libc::sockaddr_in6>() };
+                        u128::from_be_bytes(mask.sin6_addr.s6_addr).leading_ones() as u8
+                    };
+                    IpNetwork::new(Ipv6Addr::from_in6_addr(addr.sin6_addr), prefix_len)
+                }
+                _ => continue,
+            };
+            addresses.extend(network.ok());
+        }
+        unsafe { libc::freeifaddrs(ifap) };
+        Ok(addresses)
+    }
 }
 
-impl Drop for Interface {
-    fn drop(&mut self) {
-        unsafe { libc::close(self.socket) };
+/// Returns the IPv4 address of an IPv4 [`netlink::Address`] field.
+fn ipv4(address: IpAddr) -> Ipv4Addr {
+    match address {
+        IpAddr::V4(address) => address,
+        IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+    }
+}
+
+/// Changes the address as `SIOCSIFADDR` does: unless the address is unchanged, the peer is
+/// cleared, the prefix length is reset to the classful one (`32` on point-to-point links) and
+/// the broadcast address is derived from it.
+fn set_address(primary: &mut netlink::Address, address: Ipv4Addr, link_flags: u32) -> Result<()> {
+    if primary.local.address() == IpAddr::V4(address) {
+        return Ok(());
+    }
+    let prefix_len = if link_flags & libc::IFF_POINTOPOINT as u32 != 0 {
+        32
+    } else {
+        classful_prefix_len(address)?
+    };
+    primary.local = IpNetwork::new(address, prefix_len)?;
+    primary.peer = None;
+    primary.broadcast = default_broadcast(primary.local, link_flags);
+    Ok(())
+}
+
+/// Changes the netmask as `SIOCSIFNETMASK` does, updating the broadcast address unless it was
+/// set to something else than the one derived from the previous netmask.
+fn set_netmask(primary: &mut netlink::Address, netmask: Ipv4Addr, link_flags: u32) -> Result<()> {
+    let mask = u32::from(netmask);
+    if mask.leading_ones() + mask.trailing_zeros() != 32 {
+        return Err(Error::InvalidNetwork(format!("netmask {netmask}")));
+    }
+    let previous = primary.local;
+    primary.local = IpNetwork::new(previous.address(), mask.leading_ones() as u8)?;
+    if let Some(broadcast) = default_broadcast(primary.local, link_flags)
+        && primary.broadcast == Some(broadcast_address(previous))
+    {
+        primary.broadcast = Some(broadcast);
+    }
+    Ok(())
+}
+
+/// Returns the prefix length the kernel assigns to `address` in the absence of a netmask.
+fn classful_prefix_len(address: Ipv4Addr) -> Result<u8> {
+    match address.octets()[0] {
+        _ if address.is_broadcast() => Ok(0),
+        0 => Ok(0),
+        1..128 => Ok(8),
+        128..192 => Ok(16),
+        192..224 => Ok(24),
+        224..240 => Err(Error::InvalidNetwork(format!(
+            "multicast address {address}"
+        ))),
+        240.. => Ok(32),
+    }
+}
+
+/// Returns the broadcast address the kernel derives for `local` on links with `IFF_BROADCAST`.
+fn default_broadcast(local: IpNetwork, link_flags: u32) -> Option<IpAddr> {
+    (link_flags & libc::IFF_BROADCAST as u32 != 0 && local.prefix_len() < 31)
+        .then(|| broadcast_address(local))
+}
+
+fn broadcast_address(local: IpNetwork) -> IpAddr {
+    let mask = u32::from(ipv4(local.netmask()));
+    Ipv4Addr::from(u32::from(ipv4(local.address())) | !mask).into()
+}
+
+/// Opens a datagram socket of the given family to issue `SIOC*` requests.
+fn socket(domain: i32, cloexec: bool) -> Result<OwnedFd> {
+    let extra_flags = if cloexec { libc::SOCK_CLOEXEC } else { 0 };
+    match unsafe { libc::socket(domain, libc::SOCK_DGRAM | extra_flags, 0) } {
+        fd if fd >= 0 => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+        _ => Err(io::Error::last_os_error().into()),
+    }
+}
+
+/// Opens a new file descriptor of the clone device.
+pub fn open(cloexec: bool) -> Result<OwnedFd> {
+    let extra_flags = if cloexec { libc::O_CLOEXEC } else { 0 };
+    match unsafe {
+        libc::open(
+            TUN.as_ptr().cast::<c_char>(),
+            libc::O_RDWR | libc::O_NONBLOCK | extra_flags,
+        )
+    } {
+        fd if fd >= 0 => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+        _ => Err(Error::OpenDevice(io::Error::last_os_error())),
+    }
+}
+
+/// Verifies that `name` is accepted by the kernel as the name of a device, or as a name
+/// template (e.g. `tun%d`) if `template` is `true`.
+pub fn validate_name(name: &str, template: bool) -> Result<()> {
+    let invalid = |reason: &str| {
+        Err(Error::InvalidName {
+            name: name.to_owned(),
+            reason: reason.to_owned(),
+        })
+    };
+    if name.is_empty() {
+        return invalid("empty name");
+    }
+    if name.len() >= libc::IFNAMSIZ {
+        return invalid("longer than 15 bytes");
+    }
+    if name == "." || name == ".." {
+        return invalid("reserved name");
+    }
+    if name.contains(['/', ':']) || name.chars().any(char::is_whitespace) {
+        return invalid("contains '/', ':' or whitespace");
+    }
+    match name.split_once('%') {
+        Some(_) if !template => invalid("name templates are not allowed"),
+        Some((_, rest)) if !rest.starts_with('d') || rest[1..].contains('%') => {
+            invalid("a template must contain a single `%d`")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Flags of an existing device which must be passed to `TUNSETIFF` when attaching to it.
+pub const ATTACH_FLAGS: i16 =
+    (libc::IFF_TUN | libc::IFF_TAP | libc::IFF_NO_PI | libc::IFF_VNET_HDR | libc::IFF_MULTI_QUEUE)
+        as i16;
+
+/// Returns the `IFF_*` flags of an existing Tun/Tap device, as exposed in
+/// `/sys/class/net/<name>/tun_flags`.
+pub fn device_flags(name: &str) -> Result<i16> {
+    let invalid = |reason: String| Error::InvalidDevice {
+        interface: name.to_owned(),
+        reason,
+    };
+    let flags = match fs::read_to_string(format!("/sys/class/net/{name}/tun_flags")) {
+        Ok(flags) => flags,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            return Err(invalid(
+                if fs::metadata(format!("/sys/class/net/{name}")).is_ok() {
+                    "not a TUN/TAP device"
+                } else {
+                    "no such device"
+                }
+                .into(),
+            ));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let flags = flags.trim();
+    u16::from_str_radix(flags.trim_start_matches("0x"), 16)
+        .map(|flags| flags as i16)
+        .map_err(|_| invalid(format!("invalid tun_flags `{flags}`")))
+}
+
+/// Verifies that an existing device matches the TUN/TAP type, queue mode, packet information and
+/// vnet header flags of `flags`.
+pub fn check_device(name: &str, flags: i16) -> Result<()> {
+    let existing = device_flags(name)?;
+    let invalid = |reason: String| Error::InvalidDevice {
+        interface: name.to_owned(),
+        reason,
+    };
+    let kind = |flags: i16| {
+        if flags & libc::IFF_TAP as i16 != 0 {
+            "TAP"
+        } else {
+            "TUN"
+        }
+    };
+    if kind(existing) != kind(flags) {
+        return Err(invalid(format!(
+            "a {} device, not a {} device",
+            kind(existing),
+            kind(flags)
+        )));
+    }
+    let multi_queue = |flags: i16| flags & libc::IFF_MULTI_QUEUE as i16 != 0;
+    if multi_queue(existing) != multi_queue(flags) {
+        return Err(invalid(if multi_queue(existing) {
+            "a multi-queue device".into()
+        } else {
+            "not a multi-queue device".into()
+        }));
+    }
+    // `TUNSETIFF` would silently change the framing of the queues already attached.
+    let packet_info = |flags: i16| flags & libc::IFF_NO_PI as i16 == 0;
+    if packet_info(existing) != packet_info(flags) {
+        return Err(invalid(if packet_info(existing) {
+            "packet information is enabled on the device".into()
+        } else {
+            "packet information is disabled on the device".into()
+        }));
+    }
+    let vnet_hdr = |flags: i16| flags & libc::IFF_VNET_HDR as i16 != 0;
+    if vnet_hdr(existing) != vnet_hdr(flags) {
+        return Err(invalid(if vnet_hdr(existing) {
+            "vnet headers are enabled on the device".into()
+        } else {
+            "vnet headers are disabled on the device".into()
+        }));
+    }
+    Ok(())
+}
+
+/// Attaches `fd` to the existing device `name` and clears its persistent flag, so that the
+/// device is removed once `fd` is closed.
+pub fn unpersist(fd: BorrowedFd<'_>, name: &str) -> Result<()> {
+    let mut req = ifreq::new(name);
+    req.ifr_ifru.ifru_flags = device_flags(name)? & ATTACH_FLAGS;
+    unsafe { tunsetiff(fd.as_raw_fd(), &req as *const _ as _) }
+        .map_err(|err| Error::ioctl("TUNSETIFF", name, err))?;
+    unsafe { tunsetpersist(fd.as_raw_fd(), 0) }
+        .map_err(|err| Error::ioctl("TUNSETPERSIST", name, err))?;
+    Ok(())
+}
+
+/// Deletes the device `name` right away using `RTM_DELLINK`, detaching all of its queues.
+pub fn delete_link(name: &str) -> Result<()> {
+    Netlink::new(true)
+        .and_then(|netlink| netlink.request(netlink::link_message(libc::RTM_DELLINK, name, 0, 0)))
+        .map_err(|err| Error::netlink("RTM_DELLINK", name, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_names() {
+        let names = [
+            // (name, template, reason if rejected)
+            ("tun0", false, None),
+            ("tun%d", true, None),
+            ("%d", true, None),
+            ("123456789012345", false, None),
+            ("abcdefghijklmé", false, None),
+            ("", false, Some("empty name")),
+            ("1234567890123456", false, Some("longer than 15 bytes")),
+            ("abcdefghijklmné", false, Some("longer than 15 bytes")),
+            (".", false, Some("reserved name")),
+            ("..", true, Some("reserved name")),
+            ("tun/0", false, Some("contains '/', ':' or whitespace")),
+            ("tun:0", false, Some("contains '/', ':' or whitespace")),
+            ("tun 0", false, Some("contains '/', ':' or whitespace")),
+            ("tun\t0", false, Some("contains '/', ':' or whitespace")),
+            ("tun%d", false, Some("name templates are not allowed")),
+            (
+                "a%db%d",
+                true,
+                Some("a template must contain a single `%d`"),
+            ),
+            ("tun%", true, Some("a template must contain a single `%d`")),
+            ("tun%s", true, Some("a template must contain a single `%d`")),
+        ];
+        for (name, template, expected) in names {
+            let reason = match validate_name(name, template) {
+                Ok(()) => None,
+                Err(Error::InvalidName { name: n, reason }) => {
+                    assert_eq!(n, name);
+                    Some(reason)
+                }
+                Err(err) => panic!("unexpected error for `{name}`: {err}"),
+            };
+            assert_eq!(
+                reason.as_deref(),
+                expected,
+                "`{name}` (template: {template})"
+            );
+        }
+    }
+    #[test]
+    fn ipv4_defaults() {
+        let tap = (libc::IFF_BROADCAST | libc::IFF_MULTICAST) as u32;
+        let tun = (libc::IFF_POINTOPOINT | libc::IFF_NOARP) as u32;
+        let unspecified = netlink::Address::new(1, Ipv4Addr::UNSPECIFIED.into());
+        let addresses = [
+            // (address, link flags, prefix length, broadcast address)
+            ([10, 1, 2, 3], tap, 8, Some([10, 255, 255, 255])),
+            ([172, 16, 0, 1], tap, 16, Some([172, 16, 255, 255])),
+            ([192, 168, 1, 1], tap, 24, Some([192, 168, 1, 255])),
+            ([240, 0, 0, 1], tap, 32, None),
+            ([10, 1, 2, 3], tun, 32, None),
+            ([10, 1, 2, 3], 0, 8, None),
+        ];
+        for (address, link_flags, prefix_len, broadcast) in addresses {
+            let mut primary = unspecified;
+            set_address(&mut primary, address.into(), link_flags).unwrap();
+            assert_eq!(primary.local, IpNetwork::new(address, prefix_len).unwrap());
+            assert_eq!(
+                primary.broadcast,
+                broadcast.map(|b| Ipv4Addr::from(b).into())
+            );
+        }
+        let mut primary = unspecified;
+        let err = set_address(&mut primary, [224, 0, 0, 1].into(), tap).unwrap_err();
+        assert!(matches!(err, Error::InvalidNetwork(_)));
+
+        // Setting the same address again keeps the netmask and the peer.
+        let mut primary = unspecified;
+        set_address(&mut primary, [10, 1, 2, 3].into(), tap).unwrap();
+        set_netmask(&mut primary, [255, 255, 255, 0].into(), tap).unwrap();
+        primary.peer = Some(Ipv4Addr::new(10, 1, 2, 4).into());
+        let previous = primary;
+        set_address(&mut primary, [10, 1, 2, 3].into(), tap).unwrap();
+        assert_eq!(primary, previous);
+    }
+
+    #[test]
+    fn ipv4_netmask_updates_broadcast() {
+        let tap = libc::IFF_BROADCAST as u32;
+        let mut primary = netlink::Address::new(1, Ipv4Addr::UNSPECIFIED.into());
+        set_address(&mut primary, [10, 1, 2, 3].into(), tap).unwrap();
+        set_netmask(&mut primary, [255, 255, 255, 0].into(), tap).unwrap();
+        assert_eq!(primary.local.prefix_len(), 24);
+        assert_eq!(primary.broadcast, Some(Ipv4Addr::new(10, 1, 2, 255).into()));
+
+        // A /31 has no broadcast address, the previous one is kept.
+        set_netmask(&mut primary, [255, 255, 255, 254].into(), tap).unwrap();
+        assert_eq!(primary.broadcast, Some(Ipv4Addr::new(10, 1, 2, 255).into()));
+
+        // A broadcast address set explicitly is kept.
+        set_netmask(&mut primary, [255, 255, 0, 0].into(), tap).unwrap();
+        primary.broadcast = Some(Ipv4Addr::new(10, 1, 2, 255).into());
+        set_netmask(&mut primary, [255, 0, 0, 0].into(), tap).unwrap();
+        assert_eq!(primary.broadcast, Some(Ipv4Addr::new(10, 1, 2, 255).into()));
+
+        let err = set_netmask(&mut primary, [255, 0, 255, 0].into(), tap).unwrap_err();
+        assert!(matches!(err, Error::InvalidNetwork(_)));
     }
 }
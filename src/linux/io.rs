@@ -1,24 +1,36 @@
 use std::convert::From;
-use std::io::{self, IoSlice, Read, Write};
-use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
 
-pub struct TunIo(RawFd);
+pub struct TunIo(OwnedFd);
 
-impl From<RawFd> for TunIo {
-    fn from(fd: RawFd) -> Self {
+impl From<OwnedFd> for TunIo {
+    fn from(fd: OwnedFd) -> Self {
         Self(fd)
     }
 }
 
+impl From<TunIo> for OwnedFd {
+    fn from(io: TunIo) -> Self {
+        io.0
+    }
+}
+
 impl FromRawFd for TunIo {
     unsafe fn from_raw_fd(fd: RawFd) -> Self {
-        Self(fd)
+        Self(unsafe { OwnedFd::from_raw_fd(fd) })
     }
 }
 
 impl AsRawFd for TunIo {
     fn as_raw_fd(&self) -> RawFd {
-        self.0
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for TunIo {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
     }
 }
 
@@ -38,7 +50,7 @@ impl Write for TunIo {
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        let ret = unsafe { libc::fsync(self.0) };
+        let ret = unsafe { libc::fsync(self.as_raw_fd()) };
         if ret < 0 {
             return Err(io::Error::last_os_error());
         }
@@ -48,7 +60,22 @@ impl Write for TunIo {
 
 impl TunIo {
     pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        let n = unsafe { libc::read(self.0, buf.as_ptr() as *mut _, buf.len() as _) };
+        let n = unsafe { libc::read(self.as_raw_fd(), buf.as_ptr() as *mut _, buf.len() as _) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as _)
+    }
+
+    pub fn recvv(&self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let iov = bufs
+            .iter_mut()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_mut_ptr() as *mut _,
+                iov_len: buf.len() as _,
+            })
+            .collect::<Vec<_>>();
+        let n = unsafe { libc::readv(self.as_raw_fd(), iov.as_ptr() as *const _, iov.len() as _) };
         if n < 0 {
             return Err(io::Error::last_os_error());
         }
@@ -56,7 +83,7 @@ impl TunIo {
     }
 
     pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
-        let n = unsafe { libc::write(self.0, buf.as_ptr() as *const _, buf.len() as _) };
+        let n = unsafe { libc::write(self.as_raw_fd(), buf.as_ptr() as *const _, buf.len() as _) };
         if n < 0 {
             return Err(io::Error::last_os_error());
         }
@@ -71,16 +98,10 @@ impl TunIo {
                 iov_len: buf.len() as _,
             })
             .collect::<Vec<_>>();
-        let n = unsafe { libc::writev(self.0, iov.as_ptr() as *const _, iov.len() as _) };
+        let n = unsafe { libc::writev(self.as_raw_fd(), iov.as_ptr() as *const _, iov.len() as _) };
         if n < 0 {
             return Err(io::Error::last_os_error());
         }
         Ok(n as _)
     }
 }
-
-impl Drop for TunIo {
-    fn drop(&mut self) {
-        unsafe { libc::close(self.0) };
-    }
-}
@@ -0,0 +1,539 @@
+#![allow(non_camel_case_types)]
+
+use crate::{InterfaceStats, IpNetwork, Route};
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr;
+use std::sync::Mutex;
+
+const NLMSG_HDRLEN: usize = mem::size_of::<nlmsghdr>();
+const RTA_HDRLEN: usize = mem::size_of::<rtattr>();
+pub const RECV_BUF_LEN: usize = 64 * 1024;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct nlmsghdr {
+    pub nlmsg_len: u32,
+    pub nlmsg_type: u16,
+    pub nlmsg_flags: u16,
+    pub nlmsg_seq: u32,
+    pub nlmsg_pid: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct rtattr {
+    rta_len: u16,
+    rta_type: u16,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ifaddrmsg {
+    pub ifa_family: u8,
+    pub ifa_prefixlen: u8,
+    pub ifa_flags: u8,
+    pub ifa_scope: u8,
+    pub ifa_index: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ifinfomsg {
+    pub ifi_family: u8,
+    pub ifi_pad: u8,
+    pub ifi_type: u16,
+    pub ifi_index: i32,
+    pub ifi_flags: u32,
+    pub ifi_change: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rtmsg {
+    pub rtm_family: u8,
+    pub rtm_dst_len: u8,
+    pub rtm_src_len: u8,
+    pub rtm_tos: u8,
+    pub rtm_table: u8,
+    pub rtm_protocol: u8,
+    pub rtm_scope: u8,
+    pub rtm_type: u8,
+    pub rtm_flags: u32,
+}
+
+const fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Reads a C struct from the beginning of a (possibly unaligned) netlink payload.
+pub fn read_header<T: Copy>(payload: &[u8]) -> Option<T> {
+    if payload.len() < mem::size_of::<T>() {
+        return None;
+    }
+    Some(unsafe { ptr::read_unaligned(payload.as_ptr().cast::<T>()) })
+}
+
+/// Represents a netlink request being built.
+pub struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    pub fn new<T: Copy>(ty: u16, flags: i32, header: T) -> Self {
+        let hdr = nlmsghdr {
+            nlmsg_len: 0,
+            nlmsg_type: ty,
+            nlmsg_flags: (libc::NLM_F_REQUEST | flags) as u16,
+            nlmsg_seq: 0,
+            nlmsg_pid: 0,
+        };
+        let mut msg = Self { buf: Vec::new() };
+        msg.put(&hdr);
+        msg.put(&header);
+        msg
+    }
+
+    pub fn attr(mut self, ty: u16, data: &[u8]) -> Self {
+        let hdr = rtattr {
+            rta_len: (RTA_HDRLEN + data.len()) as u16,
+            rta_type: ty,
+        };
+        self.put(&hdr);
+        self.buf.extend_from_slice(data);
+        self.buf.resize(align(self.buf.len()), 0);
+        self
+    }
+
+    pub fn ip_attr(self, ty: u16, address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => self.attr(ty, &address.octets()),
+            IpAddr::V6(address) => self.attr(ty, &address.octets()),
+        }
+    }
+
+    pub fn u32_attr(self, ty: u16, value: u32) -> Self {
+        self.attr(ty, &value.to_ne_bytes())
+    }
+
+    pub fn str_attr(self, ty: u16, value: &str) -> Self {
+        self.attr(ty, &[value.as_bytes(), &[0]].concat())
+    }
+
+    fn flag(mut self, flag: i32) -> Self {
+        let flags = u16::from_ne_bytes([self.buf[6], self.buf[7]]) | flag as u16;
+        self.buf[6..8].copy_from_slice(&flags.to_ne_bytes());
+        self
+    }
+
+    fn put<T: Copy>(&mut self, value: &T) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts((value as *const T).cast::<u8>(), mem::size_of::<T>())
+        };
+        self.buf.extend_from_slice(bytes);
+        self.buf.resize(align(self.buf.len()), 0);
+    }
+
+    fn finish(&mut self, seq: u32) -> &[u8] {
+        let len = self.buf.len() as u32;
+        self.buf[0..4].copy_from_slice(&len.to_ne_bytes());
+        self.buf[8..12].copy_from_slice(&seq.to_ne_bytes());
+        &self.buf
+    }
+}
+
+/// Iterates over the route attributes of a netlink payload.
+pub struct Attrs<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Attrs<'a> {
+    /// Creates an iterator over the attributes following a `T` header in `payload`.
+    pub fn after<T>(payload: &'a [u8]) -> Self {
+        Self {
+            buf: payload
+                .get(align(mem::size_of::<T>())..)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hdr = read_header::<rtattr>(self.buf)?;
+        let len = hdr.rta_len as usize;
+        if len < RTA_HDRLEN || len > self.buf.len() {
+            return None;
+        }
+        let data = &self.buf[RTA_HDRLEN..len];
+        self.buf = self.buf.get(align(len)..).unwrap_or_default();
+        Some((hdr.rta_type, data))
+    }
+}
+
+/// Parses an IPv4 or IPv6 address from an attribute payload.
+pub fn parse_ip(data: &[u8]) -> Option<IpAddr> {
+    match data.len() {
+        4 => Some(Ipv4Addr::from(<[u8; 4]>::try_from(data).ok()?).into()),
+        16 => Some(Ipv6Addr::from(<[u8; 16]>::try_from(data).ok()?).into()),
+        _ => None,
+    }
+}
+
+/// Iterates over the messages of a netlink datagram.
+pub struct Messages<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Messages<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = (nlmsghdr, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hdr = read_header::<nlmsghdr>(self.buf)?;
+        let len = hdr.nlmsg_len as usize;
+        if len < NLMSG_HDRLEN || len > self.buf.len() {
+            return None;
+        }
+        let payload = &self.buf[NLMSG_HDRLEN..len];
+        self.buf = self.buf.get(align(len)..).unwrap_or_default();
+        Some((hdr, payload))
+    }
+}
+
+/// Opens a `NETLINK_ROUTE` socket subscribed to the given multicast `groups`.
+fn socket(flags: i32, groups: u32) -> io::Result<OwnedFd> {
+    let socket = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | flags,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if socket < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(socket) };
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as _;
+    addr.nl_groups = groups;
+    let ret = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            (&addr as *const libc::sockaddr_nl).cast(),
+            mem::size_of::<libc::sockaddr_nl>() as _,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
+/// Opens a non-blocking socket receiving the notifications about links and their addresses.
+pub fn subscribe(cloexec: bool) -> io::Result<OwnedFd> {
+    let extra_flags = if cloexec { libc::SOCK_CLOEXEC } else { 0 };
+    let groups = libc::RTMGRP_LINK | libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR;
+    socket(libc::SOCK_NONBLOCK | extra_flags, groups as u32)
+}
+
+/// Represents a `NETLINK_ROUTE` socket.
+///
+/// Requests are serialized, so a single socket can be shared between queues.
+pub struct Netlink {
+    socket: OwnedFd,
+    seq: Mutex<u32>,
+}
+
+impl Netlink {
+    pub fn new(cloexec: bool) -> io::Result<Self> {
+        let extra_flags = if cloexec { libc::SOCK_CLOEXEC } else { 0 };
+        Ok(Self {
+            socket: socket(extra_flags, 0)?,
+            seq: Mutex::new(0),
+        })
+    }
+
+    /// Sends a request and waits for its acknowledgement.
+    pub fn request(&self, msg: Message) -> io::Result<()> {
+        self.transact(msg.flag(libc::NLM_F_ACK), |_, _| {})
+    }
+
+    /// Sends a request and returns the payload of its (single) reply.
+    pub fn get(&self, msg: Message) -> io::Result<Vec<u8>> {
+        let mut reply = None;
+        self.transact(msg.flag(libc::NLM_F_ACK), |_, payload| {
+            reply.get_or_insert_with(|| payload.to_vec());
+        })?;
+        reply.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+
+    /// Sends a dump request and returns the `(type, payload)` of every message in the reply.
+    pub fn dump(&self, msg: Message) -> io::Result<Vec<(u16, Vec<u8>)>> {
+        let mut messages = Vec::new();
+        self.transact(msg.flag(libc::NLM_F_DUMP), |ty, payload| {
+            messages.push((ty, payload.to_vec()))
+        })?;
+        Ok(messages)
+    }
+
+    fn transact(&self, mut msg: Message, mut f: impl FnMut(u16, &[u8])) -> io::Result<()> {
+        let mut seq = self.seq.lock().unwrap_or_else(|err| err.into_inner());
+        *seq = seq.wrapping_add(1);
+        let buf = msg.finish(*seq);
+        if unsafe { libc::send(self.socket.as_raw_fd(), buf.as_ptr().cast(), buf.len(), 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; RECV_BUF_LEN];
+        loop {
+            let n = unsafe {
+                libc::recv(
+                    self.socket.as_raw_fd(),
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                    0,
+                )
+            };
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            for (hdr, payload) in Messages::new(&buf[..n as usize]) {
+                if hdr.nlmsg_seq != *seq {
+                    continue;
+                }
+                match hdr.nlmsg_type as i32 {
+                    libc::NLMSG_DONE => return Ok(()),
+                    libc::NLMSG_ERROR => {
+                        return match read_header::<i32>(payload) {
+                            Some(0) => Ok(()),
+                            Some(errno) => Err(io::Error::from_raw_os_error(-errno)),
+                            None => Err(io::Error::from(io::ErrorKind::InvalidData)),
+                        };
+                    }
+                    _ => f(hdr.nlmsg_type, payload),
+                }
+            }
+        }
+    }
+}
+
+pub const IFLA_ADDRESS: u16 = 1;
+pub const IFLA_IFNAME: u16 = 3;
+pub const IFLA_MTU: u16 = 4;
+pub const IFLA_TXQLEN: u16 = 13;
+pub const IFLA_STATS64: u16 = 23;
+pub const IFLA_NET_NS_FD: u16 = 28;
+pub const IFA_BROADCAST: u16 = 4;
+
+/// Builds an `RTM_GETLINK`/`RTM_NEWLINK` request for the interface `name`, changing its
+/// `change` flags to `flags` in the latter case.
+pub fn link_message(ty: u16, name: &str, flags: u32, change: u32) -> Message {
+    let header = ifinfomsg {
+        ifi_family: libc::AF_UNSPEC as _,
+        ifi_pad: 0,
+        ifi_type: 0,
+        ifi_index: 0,
+        ifi_flags: flags,
+        ifi_change: change,
+    };
+    Message::new(ty, 0, header).str_attr(IFLA_IFNAME, name)
+}
+
+/// Represents the attributes of a link reported by `RTM_GETLINK`.
+pub struct Link {
+    pub index: u32,
+    pub name: Option<String>,
+    pub flags: u32,
+    pub mtu: Option<u32>,
+    pub mac_address: Option<[u8; 6]>,
+    pub txqueuelen: Option<u32>,
+    pub stats: Option<InterfaceStats>,
+}
+
+/// Parses an `RTM_NEWLINK` payload.
+pub fn parse_link(payload: &[u8]) -> Option<Link> {
+    let header = read_header::<ifinfomsg>(payload)?;
+    let mut link = Link {
+        index: header.ifi_index as u32,
+        name: None,
+        flags: header.ifi_flags,
+        mtu: None,
+        mac_address: None,
+        txqueuelen: None,
+        stats: None,
+    };
+    for (ty, data) in Attrs::after::<ifinfomsg>(payload) {
+        match ty {
+            IFLA_IFNAME => {
+                let name = data.split(|&b| b == 0).next().unwrap_or_default();
+                link.name = Some(String::from_utf8_lossy(name).into_owned());
+            }
+            IFLA_MTU => link.mtu = read_header::<u32>(data),
+            IFLA_TXQLEN => link.txqueuelen = read_header::<u32>(data),
+            IFLA_ADDRESS => link.mac_address = data.try_into().ok(),
+            IFLA_STATS64 => {
+                link.stats = read_header::<[u64; 10]>(data).map(InterfaceStats::from_values)
+            }
+            _ => {}
+        }
+    }
+    Some(link)
+}
+
+/// Represents an address of an interface along with its peer and broadcast addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Address {
+    pub index: u32,
+    pub local: IpNetwork,
+    pub peer: Option<IpAddr>,
+    pub broadcast: Option<IpAddr>,
+}
+
+impl Address {
+    pub fn new(index: u32, local: IpNetwork) -> Self {
+        Self {
+            index,
+            local,
+            peer: None,
+            broadcast: None,
+        }
+    }
+}
+
+/// Builds an `RTM_NEWADDR`/`RTM_DELADDR` request for the given address.
+pub fn address_message(ty: u16, flags: i32, address: &Address) -> Message {
+    let local = address.local.address();
+    let header = ifaddrmsg {
+        ifa_family: family(local),
+        ifa_prefixlen: address.local.prefix_len(),
+        ifa_flags: 0,
+        ifa_scope: libc::RT_SCOPE_UNIVERSE,
+        ifa_index: address.index,
+    };
+    let msg = Message::new(ty, flags, header)
+        .ip_attr(libc::IFA_LOCAL, local)
+        .ip_attr(libc::IFA_ADDRESS, address.peer.unwrap_or(local));
+    match address.broadcast {
+        Some(broadcast) => msg.ip_attr(IFA_BROADCAST, broadcast),
+        None => msg,
+    }
+}
+
+/// Parses an `RTM_NEWADDR` payload.
+pub fn parse_address(payload: &[u8]) -> Option<Address> {
+    let header = read_header::<ifaddrmsg>(payload)?;
+    let mut local = None;
+    let mut address = None;
+    let mut broadcast = None;
+    for (ty, data) in Attrs::after::<ifaddrmsg>(payload) {
+        match ty {
+            libc::IFA_LOCAL => local = parse_ip(data),
+            libc::IFA_ADDRESS => address = parse_ip(data),
+            IFA_BROADCAST => broadcast = parse_ip(data),
+            _ => {}
+        }
+    }
+    // On point-to-point links `IFA_ADDRESS` holds the peer address.
+    let peer = address.filter(|address| local.is_some_and(|local| local != *address));
+    Some(Address {
+        index: header.ifa_index,
+        local: IpNetwork::new(local.or(address)?, header.ifa_prefixlen).ok()?,
+        peer,
+        broadcast,
+    })
+}
+
+/// Builds an `RTM_NEWROUTE`/`RTM_DELROUTE` request for a route through the interface with the
+/// given index, filling the header like `ip route add/del` does.
+pub fn route_message(ty: u16, flags: i32, index: u32, route: &Route) -> Message {
+    let destination = route.destination.network();
+    let table = route.table.unwrap_or(libc::RT_TABLE_MAIN as u32);
+    let add = ty == libc::RTM_NEWROUTE;
+    let header = rtmsg {
+        rtm_family: family(destination),
+        rtm_dst_len: route.destination.prefix_len(),
+        rtm_src_len: 0,
+        rtm_tos: 0,
+        rtm_table: if table < 256 {
+            table as u8
+        } else {
+            libc::RT_TABLE_UNSPEC
+        },
+        rtm_protocol: if add { libc::RTPROT_BOOT } else { 0 },
+        rtm_scope: match (add, route.gateway, destination) {
+            (false, _, _) => libc::RT_SCOPE_NOWHERE,
+            (true, None, IpAddr::V4(_)) => libc::RT_SCOPE_LINK,
+            (true, _, _) => libc::RT_SCOPE_UNIVERSE,
+        },
+        rtm_type: if add { libc::RTN_UNICAST } else { 0 },
+        rtm_flags: 0,
+    };
+    let mut msg = Message::new(ty, flags, header)
+        .ip_attr(libc::RTA_DST, destination)
+        .u32_attr(libc::RTA_OIF, index)
+        .u32_attr(libc::RTA_TABLE, table);
+    if let Some(gateway) = route.gateway {
+        msg = msg.ip_attr(libc::RTA_GATEWAY, gateway);
+    }
+    if let Some(metric) = route.metric {
+        msg = msg.u32_attr(libc::RTA_PRIORITY, metric);
+    }
+    msg
+}
+
+/// Parses an `RTM_NEWROUTE` payload, returning the index of the output interface along with
+/// the route. Only unicast routes outside of the local table are returned.
+pub fn parse_route(payload: &[u8]) -> Option<(u32, Route)> {
+    let header = read_header::<rtmsg>(payload)?;
+    if header.rtm_type != libc::RTN_UNICAST {
+        return None;
+    }
+    let mut index = None;
+    let mut destination = None;
+    let mut gateway = None;
+    let mut table = header.rtm_table as u32;
+    let mut metric = None;
+    for (ty, data) in Attrs::after::<rtmsg>(payload) {
+        match ty {
+            libc::RTA_OIF => index = read_header::<u32>(data),
+            libc::RTA_DST => destination = parse_ip(data),
+            libc::RTA_GATEWAY => gateway = parse_ip(data),
+            libc::RTA_TABLE => table = read_header::<u32>(data)?,
+            libc::RTA_PRIORITY => metric = read_header::<u32>(data),
+            _ => {}
+        }
+    }
+    if table == libc::RT_TABLE_LOCAL as u32 {
+        return None;
+    }
+    let destination = match (destination, header.rtm_family as i32) {
+        (Some(destination), _) => destination,
+        (None, libc::AF_INET) => Ipv4Addr::UNSPECIFIED.into(),
+        (None, libc::AF_INET6) => Ipv6Addr::UNSPECIFIED.into(),
+        _ => return None,
+    };
+    let route = Route {
+        destination: IpNetwork::new(destination, header.rtm_dst_len).ok()?,
+        gateway,
+        table: Some(table).filter(|&table| table != libc::RT_TABLE_MAIN as u32),
+        metric,
+    };
+    Some((index?, route))
+}
+
+fn family(address: IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => libc::AF_INET as u8,
+        IpAddr::V6(_) => libc::AF_INET6 as u8,
+    }
+}
@@ -1,3 +1,4 @@
+use crate::{InterfaceFlags, IpNetwork, NetNs, OffloadFlags, Route};
 use std::net::Ipv4Addr;
 
 /// Represents parameters for creating a new Tun/Tap device on Linux.
@@ -6,13 +7,23 @@ pub struct Params {
     pub name: Option<String>,
     pub flags: i16,
     pub persist: bool,
+    pub attach: bool,
     pub up: bool,
+    pub interface_flags: InterfaceFlags,
+    pub offload: Option<OffloadFlags>,
     pub mtu: Option<i32>,
+    pub txqueuelen: Option<u32>,
     pub owner: Option<i32>,
     pub group: Option<i32>,
+    pub mac_address: Option<[u8; 6]>,
     pub address: Option<Ipv4Addr>,
     pub destination: Option<Ipv4Addr>,
     pub broadcast: Option<Ipv4Addr>,
     pub netmask: Option<Ipv4Addr>,
+    pub addresses: Vec<IpNetwork>,
+    pub routes: Vec<Route>,
+    pub remove_routes: bool,
+    pub netns: Option<NetNs>,
+    pub queue_stats: bool,
     pub cloexec: bool,
 }
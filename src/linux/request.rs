@@ -3,8 +3,9 @@
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 
+use crate::{Error, Result};
 use std::os::raw::{c_char, c_int, c_short, c_uchar, c_ulong, c_ushort};
-use std::{ffi::CStr, mem, ptr, str};
+use std::{ffi::CStr, mem, ptr};
 
 const IFNAMSIZ: u32 = 16;
 
@@ -62,10 +63,12 @@ impl ifreq {
     pub fn new(name: &str) -> Self {
         let mut req: ifreq = unsafe { mem::zeroed() };
         if !name.is_empty() {
-            let len = name.len().min(IFNAMSIZ as usize - 1);
-            // Done just to make sure we don't truncate
-            // on an UTF-8 code point boundary.
-            let name = &name[..len];
+            // Names are validated beforehand, this only makes sure we never write past the
+            // buffer nor truncate on an UTF-8 code point boundary.
+            let mut len = name.len().min(IFNAMSIZ as usize - 1);
+            while !name.is_char_boundary(len) {
+                len -= 1;
+            }
             unsafe {
                 ptr::copy_nonoverlapping(
                     name.as_ptr().cast::<c_char>(),
@@ -77,9 +80,33 @@ impl ifreq {
         req
     }
 
-    pub fn name(&self) -> &str {
-        unsafe {
-            str::from_utf8_unchecked(CStr::from_ptr(self.ifr_ifrn.ifrn_name.as_ptr()).to_bytes())
+    /// Returns the name set by the kernel, which may contain any byte but `/`, `:`, whitespace
+    /// and NUL, so that names which are not valid UTF-8 are rejected.
+    pub fn name(&self) -> Result<&str> {
+        let name = unsafe { CStr::from_ptr(self.ifr_ifrn.ifrn_name.as_ptr()) };
+        name.to_str().map_err(|_| Error::InvalidDevice {
+            interface: name.to_string_lossy().into_owned(),
+            reason: "name is not valid UTF-8".into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name() {
+        let req = ifreq::new("tun0");
+        assert_eq!(req.name().unwrap(), "tun0");
+
+        let mut req = ifreq::new("");
+        for (dst, &src) in unsafe { req.ifr_ifrn.ifrn_name.iter_mut() }.zip(b"t\xffx") {
+            *dst = src as c_char;
+        }
+        match req.name() {
+            Err(Error::InvalidDevice { interface, .. }) => assert_eq!(interface, "t\u{fffd}x"),
+            res => panic!("unexpected result: {res:?}"),
         }
     }
 }
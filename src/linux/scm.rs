@@ -0,0 +1,279 @@
+use crate::Result;
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::ptr;
+
+const MAGIC: &[u8; 4] = b"TTUN";
+const VERSION: u8 = 1;
+/// Maximum number of file descriptors the kernel accepts in a single message (`SCM_MAX_FD`).
+pub const MAX_FDS: usize = 253;
+/// Magic, version, number of queues and length of the name.
+const HEADER_LEN: usize = 7;
+const IFNAMSIZ: usize = 16;
+
+/// Represents the metadata sent along with the queues of a device.
+#[derive(Debug)]
+pub struct Metadata {
+    pub name: String,
+    pub queues: usize,
+}
+
+impl Metadata {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(HEADER_LEN + self.name.len());
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.push(self.queues as u8);
+        buf.push(self.name.len() as u8);
+        buf.extend_from_slice(self.name.as_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(ErrorKind::InvalidData, msg.to_owned());
+        if buf.len() < HEADER_LEN || &buf[..4] != MAGIC {
+            return Err(invalid("invalid device handoff message"));
+        }
+        if buf[4] != VERSION {
+            return Err(invalid("unsupported device handoff version"));
+        }
+        let name = buf
+            .get(HEADER_LEN..HEADER_LEN + buf[6] as usize)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| invalid("invalid device name in handoff message"))?;
+        Ok(Self {
+            name: name.to_owned(),
+            queues: buf[5] as usize,
+        })
+    }
+}
+
+/// Returns a zeroed control message buffer for `len` bytes of data, aligned for `cmsghdr`.
+fn control_buf(len: usize) -> Vec<u64> {
+    let space = unsafe { libc::CMSG_SPACE(len as _) } as usize;
+    vec![0; space.div_ceil(mem::size_of::<u64>())]
+}
+
+/// Sends the metadata of a device along with its queue file descriptors as `SCM_RIGHTS`.
+pub fn send(stream: &UnixStream, metadata: &Metadata, fds: &[OwnedFd]) -> Result<()> {
+    if fds.is_empty() || fds.len() > MAX_FDS {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("cannot send {} queues, expected 1 to {MAX_FDS}", fds.len()),
+        )
+        .into());
+    }
+    let data = metadata.encode();
+    let fds = fds.iter().map(AsRawFd::as_raw_fd).collect::<Vec<_>>();
+    let fds_len = mem::size_of_val(fds.as_slice());
+    let mut control = control_buf(fds_len);
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut _,
+        iov_len: data.len(),
+    };
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = mem::size_of_val(control.as_slice()) as _;
+    unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len as _) as _;
+        ptr::copy_nonoverlapping(
+            fds.as_ptr(),
+            libc::CMSG_DATA(cmsg).cast::<RawFd>(),
+            fds.len(),
+        );
+    }
+    loop {
+        match unsafe { libc::sendmsg(stream.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) } {
+            n if n < 0 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != ErrorKind::Interrupted {
+                    return Err(err.into());
+                }
+            }
+            n if (n as usize) < data.len() => {
+                return Err(io::Error::from(ErrorKind::WriteZero).into());
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Receives the metadata of a device along with its queue file descriptors.
+pub fn recv(stream: &UnixStream) -> Result<(Metadata, Vec<OwnedFd>)> {
+    let mut data = [0u8; HEADER_LEN + IFNAMSIZ];
+    let mut control = control_buf(MAX_FDS * mem::size_of::<RawFd>());
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = mem::size_of_val(control.as_slice()) as _;
+    let n = loop {
+        match unsafe { libc::recvmsg(stream.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) } {
+            n if n < 0 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != ErrorKind::Interrupted {
+                    return Err(err.into());
+                }
+            }
+            0 => return Err(io::Error::from(ErrorKind::UnexpectedEof).into()),
+            n => break n as usize,
+        }
+    };
+
+    // Take ownership of the received descriptors first, so that they are closed on error.
+    let mut fds = Vec::new();
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let hdr = unsafe { &*cmsg };
+        if hdr.cmsg_level == libc::SOL_SOCKET && hdr.cmsg_type == libc::SCM_RIGHTS {
+            let len = hdr.cmsg_len as usize - unsafe { libc::CMSG_LEN(0) } as usize;
+            let data = unsafe { libc::CMSG_DATA(cmsg) }.cast::<RawFd>();
+            for i in 0..len / mem::size_of::<RawFd>() {
+                let fd = unsafe { ptr::read_unaligned(data.add(i)) };
+                fds.push(unsafe { OwnedFd::from_raw_fd(fd) });
+            }
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    if msg.msg_flags & (libc::MSG_CTRUNC | libc::MSG_TRUNC) != 0 {
+        return Err(
+            io::Error::new(ErrorKind::InvalidData, "truncated device handoff message").into(),
+        );
+    }
+    let metadata = Metadata::decode(&data[..n])?;
+    if fds.len() != metadata.queues {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "expected {} queues in device handoff message, received {}",
+                metadata.queues,
+                fds.len()
+            ),
+        )
+        .into());
+    }
+    Ok((metadata, fds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::fs::File;
+    use std::io::{Read, Write};
+
+    /// Returns the read and write ends of a new non-blocking pipe.
+    fn pipe() -> (File, File) {
+        let mut fds = [0; 2];
+        let ret = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) };
+        assert_eq!(ret, 0, "{}", io::Error::last_os_error());
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    fn io_kind(err: Error) -> ErrorKind {
+        match err {
+            Error::IoError(err) => err.kind(),
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    fn metadata(queues: usize) -> Metadata {
+        Metadata {
+            name: "tun0".into(),
+            queues,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let pipes = [pipe(), pipe()];
+        let fds = pipes
+            .iter()
+            .map(|(_, w)| w.try_clone().unwrap().into())
+            .collect::<Vec<OwnedFd>>();
+        send(&a, &metadata(2), &fds).unwrap();
+
+        let (received, fds) = recv(&b).unwrap();
+        assert_eq!(received.name, "tun0");
+        assert_eq!(received.queues, 2);
+        assert_eq!(fds.len(), 2);
+        for ((mut r, _), fd) in pipes.into_iter().zip(fds) {
+            File::from(fd).write_all(b"queue").unwrap();
+            let mut buf = [0; 5];
+            r.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"queue");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_queue_count_on_send() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        assert_eq!(
+            io_kind(send(&a, &metadata(0), &[]).unwrap_err()),
+            ErrorKind::InvalidInput
+        );
+        let (_, w) = pipe();
+        let fds = (0..MAX_FDS + 1)
+            .map(|_| w.try_clone().unwrap().into())
+            .collect::<Vec<OwnedFd>>();
+        assert_eq!(
+            io_kind(send(&a, &metadata(fds.len()), &fds).unwrap_err()),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn rejects_mismatching_fd_count_and_closes_fds() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (mut r, w) = pipe();
+        send(&a, &metadata(3), &[w.into()]).unwrap();
+        assert_eq!(io_kind(recv(&b).unwrap_err()), ErrorKind::InvalidData);
+        // The only write end was received and closed again.
+        assert_eq!(r.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let mut version = metadata(1).encode();
+        version[4] = VERSION + 1;
+        let mut long_name = metadata(0).encode();
+        long_name[6] = IFNAMSIZ as u8;
+        let mut utf8 = metadata(0).encode();
+        utf8[HEADER_LEN] = 0xff;
+        let messages: [&[u8]; 5] = [
+            // Bad magic.
+            b"XTUN\x01\x00\x04tun0",
+            &version,
+            // Truncated header.
+            &MAGIC[..],
+            // Truncated name.
+            &long_name,
+            &utf8,
+        ];
+        for message in messages {
+            let (mut a, b) = UnixStream::pair().unwrap();
+            a.write_all(message).unwrap();
+            assert_eq!(io_kind(recv(&b).unwrap_err()), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn rejects_closed_stream() {
+        let (a, b) = UnixStream::pair().unwrap();
+        drop(a);
+        assert_eq!(io_kind(recv(&b).unwrap_err()), ErrorKind::UnexpectedEof);
+    }
+}
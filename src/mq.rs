@@ -0,0 +1,186 @@
+use crate::{
+    InterfaceEvents, InterfaceFlags, InterfaceStats, IpNetwork, QueueStats, Result, Route, Tun,
+};
+use std::future::poll_fn;
+use std::io;
+use std::net::Ipv4Addr;
+use std::ops::Index;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Poll;
+
+/// Represents a multi-queue Tun/Tap device owning all of its queues. Use
+/// [`TunBuilder::build_mq`](struct.TunBuilder.html#method.build_mq) to create a new instance.
+///
+/// Each queue is a [`Tun`](struct.Tun.html) which can be accessed by index or iterated over,
+/// while the interface-level getters are exposed once on this struct.
+pub struct MultiQueueTun {
+    queues: Vec<Tun>,
+    next: AtomicUsize,
+}
+
+impl MultiQueueTun {
+    pub(crate) fn new(queues: Vec<Tun>) -> Self {
+        Self {
+            queues,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of queues.
+    pub fn len(&self) -> usize {
+        self.queues.len()
+    }
+
+    /// Returns `true` if there is no queue.
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+
+    /// Returns the queue at `index`, or `None` if out of bounds.
+    pub fn get(&self, index: usize) -> Option<&Tun> {
+        self.queues.get(index)
+    }
+
+    /// Returns an iterator over the queues.
+    pub fn iter(&self) -> slice::Iter<'_, Tun> {
+        self.queues.iter()
+    }
+
+    /// Opens an additional queue and appends it to the device. Returns the index of the new
+    /// queue.
+    pub fn add_queue(&mut self) -> Result<usize> {
+        let tun = self.first().new_queue()?;
+        self.queues.push(tun);
+        Ok(self.queues.len() - 1)
+    }
+
+    /// Consumes the device, returning its queues.
+    pub fn into_inner(self) -> Vec<Tun> {
+        self.queues
+    }
+
+    /// Receives a packet from whichever queue is ready first. Returns the index of the queue and
+    /// the number of bytes written to `buf`.
+    ///
+    /// Queues are polled in a round-robin order, so that a busy queue cannot starve the others.
+    ///
+    /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, usize)> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        poll_fn(|cx| {
+            for i in 0..self.queues.len() {
+                let index = (start + i) % self.queues.len();
+                if let Poll::Ready(res) = self.queues[index].poll_recv(cx, buf) {
+                    return Poll::Ready(res.map(|n| (index, n)));
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn first(&self) -> &Tun {
+        &self.queues[0]
+    }
+
+    /// Returns the name of Tun/Tap device.
+    pub fn name(&self) -> &str {
+        self.first().name()
+    }
+
+    /// Returns the value of MTU.
+    pub fn mtu(&self) -> Result<i32> {
+        self.first().mtu()
+    }
+
+    /// Returns the length of the transmit queue.
+    pub fn txqueuelen(&self) -> Result<u32> {
+        self.first().txqueuelen()
+    }
+
+    /// Returns the statistics the kernel keeps for device, see
+    /// [`Tun::stats`](struct.Tun.html#method.stats).
+    pub fn stats(&self) -> Result<InterfaceStats> {
+        self.first().stats()
+    }
+
+    /// Returns the counters of every queue kept in the process, or `None` unless the device was
+    /// built with [`TunBuilder::queue_stats`](struct.TunBuilder.html#method.queue_stats).
+    pub fn queue_stats(&self) -> Option<Vec<QueueStats>> {
+        self.queues.iter().map(Tun::queue_stats).collect()
+    }
+
+    /// Returns the flags of device.
+    pub fn flags(&self) -> Result<InterfaceFlags> {
+        self.first().flags()
+    }
+
+    /// Returns the IPv4 address of device.
+    pub fn address(&self) -> Result<Ipv4Addr> {
+        self.first().address()
+    }
+
+    /// Returns the IPv4 destination address of device.
+    pub fn destination(&self) -> Result<Ipv4Addr> {
+        self.first().destination()
+    }
+
+    /// Returns the IPv4 broadcast address of device.
+    pub fn broadcast(&self) -> Result<Ipv4Addr> {
+        self.first().broadcast()
+    }
+
+    /// Returns the IPv4 netmask address of device.
+    pub fn netmask(&self) -> Result<Ipv4Addr> {
+        self.first().netmask()
+    }
+
+    /// Returns all IPv4 and IPv6 addresses of device along with their prefix lengths.
+    pub fn addresses(&self) -> Result<Vec<IpNetwork>> {
+        self.first().addresses()
+    }
+
+    /// Returns a stream of the changes of device, see [`Tun::events`](struct.Tun.html#method.events).
+    pub fn events(&self) -> Result<InterfaceEvents> {
+        self.first().events()
+    }
+
+    /// Returns the routes through device.
+    pub fn routes(&self) -> Result<Vec<Route>> {
+        self.first().routes()
+    }
+
+    /// Returns the MAC address of device.
+    ///
+    /// This is only supported by TAP devices.
+    pub fn mac_address(&self) -> Result<[u8; 6]> {
+        self.first().mac_address()
+    }
+}
+
+impl Index<usize> for MultiQueueTun {
+    type Output = Tun;
+
+    fn index(&self, index: usize) -> &Tun {
+        &self.queues[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a MultiQueueTun {
+    type Item = &'a Tun;
+    type IntoIter = slice::Iter<'a, Tun>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for MultiQueueTun {
+    type Item = Tun;
+    type IntoIter = std::vec::IntoIter<Tun>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.queues.into_iter()
+    }
+}
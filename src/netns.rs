@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::path::{Path, PathBuf};
+
+/// Represents a network namespace, given either by a path such as `/var/run/netns/<name>` or
+/// `/proc/<pid>/ns/net`, or by an open file descriptor of such a file.
+///
+/// See [`TunBuilder::netns`](struct.TunBuilder.html#method.netns) and
+/// [`Tun::move_to_netns`](struct.Tun.html#method.move_to_netns).
+#[derive(Debug)]
+pub enum NetNs {
+    Path(PathBuf),
+    Fd(OwnedFd),
+}
+
+impl NetNs {
+    /// Opens the namespace, borrowing the file descriptor if already open.
+    pub(crate) fn open(&self) -> io::Result<NetNsFd<'_>> {
+        match self {
+            Self::Path(path) => File::open(path).map(|file| NetNsFd::Owned(file.into())),
+            Self::Fd(fd) => Ok(NetNsFd::Borrowed(fd.as_fd())),
+        }
+    }
+}
+
+impl From<PathBuf> for NetNs {
+    fn from(path: PathBuf) -> Self {
+        Self::Path(path)
+    }
+}
+
+impl From<&Path> for NetNs {
+    fn from(path: &Path) -> Self {
+        Self::Path(path.to_owned())
+    }
+}
+
+impl From<&str> for NetNs {
+    fn from(path: &str) -> Self {
+        Self::Path(path.into())
+    }
+}
+
+impl From<OwnedFd> for NetNs {
+    fn from(fd: OwnedFd) -> Self {
+        Self::Fd(fd)
+    }
+}
+
+impl From<File> for NetNs {
+    fn from(file: File) -> Self {
+        Self::Fd(file.into())
+    }
+}
+
+pub(crate) enum NetNsFd<'a> {
+    Owned(OwnedFd),
+    Borrowed(BorrowedFd<'a>),
+}
+
+impl AsFd for NetNsFd<'_> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        match self {
+            Self::Owned(fd) => fd.as_fd(),
+            Self::Borrowed(fd) => *fd,
+        }
+    }
+}
+
+/// Switches the calling thread to another network namespace, restoring the original one when
+/// dropped. Other threads of the process are not affected.
+pub(crate) struct NetNsGuard {
+    /// `None` if the thread already was in the namespace.
+    original: Option<OwnedFd>,
+}
+
+impl NetNsGuard {
+    pub fn enter(ns: BorrowedFd<'_>) -> io::Result<Self> {
+        let original = current()?;
+        if same_file(original.as_fd(), ns)? {
+            return Ok(Self { original: None });
+        }
+        setns(ns)?;
+        Ok(Self {
+            original: Some(original),
+        })
+    }
+}
+
+impl Drop for NetNsGuard {
+    fn drop(&mut self) {
+        let Some(original) = &self.original else {
+            return;
+        };
+        // Going on in the wrong namespace would silently affect unrelated sockets.
+        if let Err(err) = setns(original.as_fd()) {
+            panic!("failed to restore the network namespace of the thread: {err}");
+        }
+    }
+}
+
+/// Opens the network namespace of the calling thread.
+pub(crate) fn current() -> io::Result<OwnedFd> {
+    File::open("/proc/thread-self/ns/net").map(OwnedFd::from)
+}
+
+fn same_file(a: BorrowedFd<'_>, b: BorrowedFd<'_>) -> io::Result<bool> {
+    let stat = |fd: BorrowedFd<'_>| {
+        let mut stat = unsafe { std::mem::zeroed::<libc::stat>() };
+        match unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } {
+            0 => Ok((stat.st_dev, stat.st_ino)),
+            _ => Err(io::Error::last_os_error()),
+        }
+    };
+    Ok(stat(a)? == stat(b)?)
+}
+
+fn setns(ns: BorrowedFd<'_>) -> io::Result<()> {
+    if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
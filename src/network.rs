@@ -0,0 +1,127 @@
+use crate::{Error, Result};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Represents an IPv4 or IPv6 address along with its prefix length, e.g. `10.0.0.1/24` or
+/// `fd00::1/64`.
+///
+/// It can be parsed from its CIDR notation. If the prefix length is omitted, the address is
+/// treated as a single host (`/32` for IPv4 and `/128` for IPv6).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    /// Creates a new instance of [`IpNetwork`](struct.IpNetwork.html).
+    ///
+    /// Returns an error if `prefix_len` is greater than `32` for IPv4 or `128` for IPv6 addresses.
+    pub fn new(address: impl Into<IpAddr>, prefix_len: u8) -> Result<Self> {
+        let address = address.into();
+        if prefix_len > max_prefix_len(&address) {
+            return Err(Error::InvalidNetwork(format!("{address}/{prefix_len}")));
+        }
+        Ok(Self {
+            address,
+            prefix_len,
+        })
+    }
+
+    /// Returns the address.
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Returns the prefix length.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns the netmask derived from the prefix length.
+    pub fn netmask(&self) -> IpAddr {
+        match self.address {
+            IpAddr::V4(_) => Ipv4Addr::from(
+                u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0),
+            )
+            .into(),
+            IpAddr::V6(_) => Ipv6Addr::from(
+                u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0),
+            )
+            .into(),
+        }
+    }
+
+    /// Returns the network address, i.e. the address with all host bits cleared.
+    pub fn network(&self) -> IpAddr {
+        match (self.address, self.netmask()) {
+            (IpAddr::V4(address), IpAddr::V4(netmask)) => (address & netmask).into(),
+            (IpAddr::V6(address), IpAddr::V6(netmask)) => (address & netmask).into(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns `true` if this is an IPv4 network.
+    pub fn is_ipv4(&self) -> bool {
+        self.address.is_ipv4()
+    }
+
+    /// Returns `true` if this is an IPv6 network.
+    pub fn is_ipv6(&self) -> bool {
+        self.address.is_ipv6()
+    }
+}
+
+fn max_prefix_len(address: &IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+impl From<IpAddr> for IpNetwork {
+    fn from(address: IpAddr) -> Self {
+        Self {
+            address,
+            prefix_len: max_prefix_len(&address),
+        }
+    }
+}
+
+impl From<Ipv4Addr> for IpNetwork {
+    fn from(address: Ipv4Addr) -> Self {
+        IpAddr::V4(address).into()
+    }
+}
+
+impl From<Ipv6Addr> for IpNetwork {
+    fn from(address: Ipv6Addr) -> Self {
+        IpAddr::V6(address).into()
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidNetwork(s.into());
+        match s.split_once('/') {
+            Some((address, prefix_len)) => Self::new(
+                address.parse::<IpAddr>().map_err(|_| invalid())?,
+                prefix_len.parse().map_err(|_| invalid())?,
+            ),
+            None => s.parse::<IpAddr>().map(Self::from).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
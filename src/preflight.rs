@@ -0,0 +1,248 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+const TUN_PATH: &str = "/dev/net/tun";
+/// Major and minor numbers of the clone device.
+const TUN_DEV: (u32, u32) = (10, 200);
+const CAP_NET_ADMIN: u32 = 12;
+
+/// Represents the state of the `/dev/net/tun` clone device node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceNode {
+    /// The node exists and can be opened for reading and writing.
+    Accessible,
+    /// The node does not exist, e.g. in a container without `--device /dev/net/tun`.
+    Missing,
+    /// The path exists but is not the `10:200` character device.
+    NotCharDevice,
+    /// The node exists but opening it failed.
+    Inaccessible(ErrorKind),
+}
+
+/// Represents the result of [`preflight`](fn.preflight.html), describing whether Tun/Tap devices
+/// can be created or attached to in the current environment.
+///
+/// The `Display` implementation prints a human readable report along with hints on how to fix
+/// detected problems.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreflightReport {
+    /// State of the `/dev/net/tun` device node.
+    pub device_node: DeviceNode,
+    /// Whether the `tun` driver is loaded (or built in), `None` if it could not be determined.
+    pub driver_loaded: Option<bool>,
+    /// Whether the process has `CAP_NET_ADMIN` in its effective capability set, `None` if it
+    /// could not be determined.
+    pub cap_net_admin: Option<bool>,
+    /// Whether the process runs in a user namespace other than the initial one, `None` if it
+    /// could not be determined.
+    pub user_namespace: Option<bool>,
+    /// Effective user id of the process.
+    pub uid: u32,
+    /// Effective group id of the process.
+    pub gid: u32,
+    /// Supplementary group ids of the process.
+    pub groups: Vec<u32>,
+}
+
+impl PreflightReport {
+    /// Returns `true` if new devices can be created, i.e. the device node is accessible and the
+    /// process has `CAP_NET_ADMIN`.
+    ///
+    /// Note that inside a user namespace, the capability only allows creating devices in a
+    /// network namespace owned by that user namespace.
+    pub fn can_create(&self) -> bool {
+        self.device_node == DeviceNode::Accessible && self.cap_net_admin == Some(true)
+    }
+
+    /// Returns `true` if the process could attach to an existing persistent device created with
+    /// the given [`owner`](struct.TunBuilder.html#method.owner) and
+    /// [`group`](struct.TunBuilder.html#method.group).
+    ///
+    /// As in the kernel, this requires either `CAP_NET_ADMIN`, or being the owner if the device
+    /// has one and being a member of the group if it has one. Anyone who can open the device node
+    /// may attach to a device with neither owner nor group.
+    pub fn can_attach(&self, owner: Option<u32>, group: Option<u32>) -> bool {
+        self.device_node == DeviceNode::Accessible
+            && (self.cap_net_admin == Some(true)
+                || (owner.is_none_or(|owner| owner == self.uid)
+                    && group.is_none_or(|group| group == self.gid || self.groups.contains(&group))))
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = |value: Option<bool>, yes: &'static str, no: &'static str| match value {
+            Some(true) => yes,
+            Some(false) => no,
+            None => "unknown",
+        };
+        write!(f, "{TUN_PATH}: ")?;
+        match self.device_node {
+            DeviceNode::Accessible => writeln!(f, "ok")?,
+            DeviceNode::Missing => writeln!(
+                f,
+                "missing (create it using `mknod {TUN_PATH} c 10 200` or pass it to the container)"
+            )?,
+            DeviceNode::NotCharDevice => writeln!(f, "not the 10:200 character device")?,
+            DeviceNode::Inaccessible(kind) => writeln!(f, "cannot be opened ({kind})")?,
+        }
+        writeln!(
+            f,
+            "tun driver: {}",
+            unknown(
+                self.driver_loaded,
+                "loaded",
+                "not loaded (try `modprobe tun`)"
+            )
+        )?;
+        writeln!(
+            f,
+            "CAP_NET_ADMIN: {}",
+            unknown(
+                self.cap_net_admin,
+                "yes",
+                "no (only persistent devices owned by this user or group can be attached)"
+            )
+        )?;
+        writeln!(
+            f,
+            "user namespace: {}",
+            unknown(self.user_namespace, "yes", "no (initial namespace)")
+        )?;
+        write!(
+            f,
+            "uid: {}, gid: {}, groups: {:?}",
+            self.uid, self.gid, self.groups
+        )
+    }
+}
+
+/// Checks whether Tun/Tap devices can be used in the current environment.
+///
+/// This inspects the `/dev/net/tun` device node, the `tun` driver, the effective capabilities
+/// and the user namespace of the process without creating any device, so that failures can be
+/// reported with actionable messages before calling [`TunBuilder::build`](struct.TunBuilder.html#method.build).
+///
+/// ```no_run
+/// let report = tokio_tun::preflight();
+/// if !report.can_create() {
+///     eprintln!("{report}");
+/// }
+/// ```
+pub fn preflight() -> PreflightReport {
+    PreflightReport {
+        device_node: device_node(),
+        driver_loaded: driver_loaded(),
+        cap_net_admin: cap_net_admin(),
+        user_namespace: user_namespace(),
+        uid: unsafe { libc::geteuid() },
+        gid: unsafe { libc::getegid() },
+        groups: groups(),
+    }
+}
+
+fn device_node() -> DeviceNode {
+    match fs::metadata(TUN_PATH) {
+        Err(err) if err.kind() == ErrorKind::NotFound => return DeviceNode::Missing,
+        Err(err) => return DeviceNode::Inaccessible(err.kind()),
+        Ok(metadata) => {
+            let rdev = metadata.rdev();
+            if !metadata.file_type().is_char_device()
+                || (libc::major(rdev), libc::minor(rdev)) != TUN_DEV
+            {
+                return DeviceNode::NotCharDevice;
+            }
+        }
+    }
+    match OpenOptions::new().read(true).write(true).open(TUN_PATH) {
+        Ok(_) => DeviceNode::Accessible,
+        Err(err) => DeviceNode::Inaccessible(err.kind()),
+    }
+}
+
+/// The driver registers the `tun` misc device once loaded.
+fn driver_loaded() -> Option<bool> {
+    let misc = fs::read_to_string("/proc/misc").ok()?;
+    Some(misc.lines().any(|line| {
+        let mut fields = line.split_whitespace();
+        fields.next() == Some("200") && fields.next() == Some("tun")
+    }))
+}
+
+fn cap_net_admin() -> Option<bool> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let caps = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?
+        .trim();
+    let caps = u64::from_str_radix(caps, 16).ok()?;
+    Some(caps & (1 << CAP_NET_ADMIN) != 0)
+}
+
+/// The initial user namespace maps the whole id range onto itself.
+fn user_namespace() -> Option<bool> {
+    let map = fs::read_to_string("/proc/self/uid_map").ok()?;
+    let fields = map.split_whitespace().collect::<Vec<_>>();
+    Some(fields != ["0", "0", "4294967295"])
+}
+
+fn groups() -> Vec<u32> {
+    let len = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if len <= 0 {
+        return Vec::new();
+    }
+    let mut groups = vec![0; len as usize];
+    let len = unsafe { libc::getgroups(len, groups.as_mut_ptr()) };
+    groups.truncate(len.max(0) as usize);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_attach() {
+        let report = PreflightReport {
+            device_node: DeviceNode::Accessible,
+            driver_loaded: Some(true),
+            cap_net_admin: Some(false),
+            user_namespace: Some(false),
+            uid: 1000,
+            gid: 1000,
+            groups: vec![1000, 27],
+        };
+        let cases = [
+            // (owner, group, expected)
+            (None, None, true),
+            (Some(1000), None, true),
+            (Some(0), None, false),
+            (None, Some(1000), true),
+            (None, Some(27), true),
+            (None, Some(0), false),
+            (Some(1000), Some(27), true),
+            (Some(1000), Some(0), false),
+            (Some(0), Some(1000), false),
+        ];
+        for (owner, group, expected) in cases {
+            assert_eq!(
+                report.can_attach(owner, group),
+                expected,
+                "owner: {owner:?}, group: {group:?}"
+            );
+        }
+
+        let admin = PreflightReport {
+            cap_net_admin: Some(true),
+            ..report.clone()
+        };
+        assert!(admin.can_attach(Some(0), Some(0)));
+        let missing = PreflightReport {
+            device_node: DeviceNode::Missing,
+            ..admin
+        };
+        assert!(!missing.can_attach(None, None));
+    }
+}
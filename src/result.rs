@@ -1,3 +1,5 @@
+use std::io;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -6,5 +8,134 @@ pub enum Error {
     NixError(#[from] nix::Error),
 
     #[error("{0}")]
-    IoError(#[from] std::io::Error),
+    IoError(#[from] io::Error),
+
+    #[error("invalid network: {0}")]
+    InvalidNetwork(String),
+
+    /// Opening the clone device `/dev/net/tun` failed.
+    #[error("failed to open /dev/net/tun: {0}")]
+    OpenDevice(#[source] io::Error),
+
+    /// Entering a network namespace failed.
+    #[error("failed to enter network namespace: {0}")]
+    NetNs(#[source] io::Error),
+
+    /// An ioctl on the device or one of its queues failed.
+    #[error("{op} failed on `{interface}`: {source}")]
+    Ioctl {
+        op: &'static str,
+        interface: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// A netlink request about the device failed.
+    #[error("{op} failed on `{interface}`: {source}")]
+    Netlink {
+        op: &'static str,
+        interface: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The name of the device is invalid.
+    #[error("invalid interface name `{name}`: {reason}")]
+    InvalidName { name: String, reason: String },
+
+    /// The device does not exist, or is not a Tun/Tap device matching the requested one.
+    #[error("invalid device `{interface}`: {reason}")]
+    InvalidDevice { interface: String, reason: String },
+
+    /// An operation was denied, usually because it requires `CAP_NET_ADMIN` or the device
+    /// belongs to another user.
+    #[error("{op} on `{interface}` is not permitted: {source}")]
+    PermissionDenied {
+        op: &'static str,
+        interface: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The device (or its only queue) is already in use, e.g. by another process.
+    #[error("{op} failed, device `{interface}` is busy")]
+    DeviceBusy { op: &'static str, interface: String },
+
+    /// The operation is not supported by the device, e.g. setting the MAC address of a TUN
+    /// device.
+    #[error("{op} is not supported by `{interface}`: {reason}")]
+    Unsupported {
+        op: &'static str,
+        interface: String,
+        reason: &'static str,
+    },
+}
+
+impl Error {
+    /// Returns `true` if the error is caused by missing privileges, e.g. when creating a device
+    /// without `CAP_NET_ADMIN`.
+    pub fn is_permission_denied(&self) -> bool {
+        match self {
+            Self::PermissionDenied { .. } => true,
+            Self::NixError(errno) => matches!(errno, nix::Error::EPERM | nix::Error::EACCES),
+            Self::IoError(err) | Self::OpenDevice(err) | Self::NetNs(err) => {
+                err.kind() == io::ErrorKind::PermissionDenied
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the name of the interface the error relates to, if known.
+    pub fn interface(&self) -> Option<&str> {
+        match self {
+            Self::Ioctl { interface, .. }
+            | Self::Netlink { interface, .. }
+            | Self::InvalidDevice { interface, .. }
+            | Self::PermissionDenied { interface, .. }
+            | Self::DeviceBusy { interface, .. }
+            | Self::Unsupported { interface, .. } => Some(interface),
+            Self::InvalidName { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Creates an error for a failed ioctl, classifying permission and busy errors.
+    pub(crate) fn ioctl(op: &'static str, interface: &str, source: impl Into<io::Error>) -> Self {
+        Self::classify(op, interface, source.into(), |op, interface, source| {
+            Self::Ioctl {
+                op,
+                interface,
+                source,
+            }
+        })
+    }
+
+    /// Creates an error for a failed netlink request, classifying permission and busy errors.
+    pub(crate) fn netlink(op: &'static str, interface: &str, source: io::Error) -> Self {
+        Self::classify(op, interface, source, |op, interface, source| {
+            Self::Netlink {
+                op,
+                interface,
+                source,
+            }
+        })
+    }
+
+    fn classify(
+        op: &'static str,
+        interface: &str,
+        source: io::Error,
+        other: impl FnOnce(&'static str, String, io::Error) -> Self,
+    ) -> Self {
+        let interface = interface.to_owned();
+        match source.raw_os_error() {
+            Some(libc::EPERM | libc::EACCES) => Self::PermissionDenied {
+                op,
+                interface,
+                source,
+            },
+            Some(libc::EBUSY) => Self::DeviceBusy { op, interface },
+            _ => other(op, interface, source),
+        }
+    }
 }
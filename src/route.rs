@@ -0,0 +1,79 @@
+use crate::IpNetwork;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Represents an IPv4 or IPv6 route through a Tun/Tap device.
+///
+/// A route can be created from its destination network, the other attributes being optional:
+///
+/// ```
+/// use tokio_tun::Route;
+///
+/// let route = Route::new("10.8.0.0/16".parse().unwrap())
+///     .with_table(100)
+///     .with_metric(10);
+/// assert_eq!(route.to_string(), "10.8.0.0/16 table 100 metric 10");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Route {
+    /// Destination network, e.g. `0.0.0.0/0` for a default route.
+    pub destination: IpNetwork,
+    /// Next hop, `None` to route directly through the device (the usual case for TUN devices).
+    pub gateway: Option<IpAddr>,
+    /// Routing table, `None` for the main table.
+    pub table: Option<u32>,
+    /// Metric (or priority), lower values being preferred. `None` for the default metric.
+    pub metric: Option<u32>,
+}
+
+impl Route {
+    /// Creates a new instance of [`Route`](struct.Route.html) to `destination` in the main table.
+    pub fn new(destination: IpNetwork) -> Self {
+        Self {
+            destination,
+            gateway: None,
+            table: None,
+            metric: None,
+        }
+    }
+
+    /// Sets the next hop.
+    pub fn with_gateway(mut self, gateway: IpAddr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Sets the routing table, e.g. one selected by a policy routing rule.
+    pub fn with_table(mut self, table: u32) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    /// Sets the metric.
+    pub fn with_metric(mut self, metric: u32) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+}
+
+impl From<IpNetwork> for Route {
+    fn from(destination: IpNetwork) -> Self {
+        Self::new(destination)
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.destination)?;
+        if let Some(gateway) = self.gateway {
+            write!(f, " via {gateway}")?;
+        }
+        if let Some(table) = self.table {
+            write!(f, " table {table}")?;
+        }
+        if let Some(metric) = self.metric {
+            write!(f, " metric {metric}")?;
+        }
+        Ok(())
+    }
+}
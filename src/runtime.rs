@@ -0,0 +1,276 @@
+use crate::linux::interface::Interface;
+use crate::{Result, Tun, TunBuilder};
+use std::future::{Future, poll_fn};
+use std::io;
+use std::mem;
+use std::os::fd::OwnedFd;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, mpsc};
+use std::task::Poll;
+use std::thread::{self, JoinHandle};
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+
+/// Size of the buffer each worker receives packets into, large enough for GSO super-packets.
+const BUF_LEN: usize = 64 * 1024 + 64;
+
+/// Represents a snapshot of the statistics of a queue worker.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// Index of the queue.
+    pub queue: usize,
+    /// CPU the worker is pinned to.
+    pub cpu: usize,
+    /// Number of packets passed to the handler.
+    pub packets: u64,
+    /// Number of bytes passed to the handler.
+    pub bytes: u64,
+    /// Number of receive errors.
+    pub errors: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+}
+
+struct Worker {
+    cpu: usize,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+/// Runs one worker thread per queue of a multi-queue device.
+///
+/// Each worker owns a current-thread tokio runtime, is pinned to its own CPU and passes every
+/// packet received on its queue to a user-supplied handler. This lets the kernel spread flows
+/// over queues (and thus CPUs) without any cross-thread synchronization on the data path.
+///
+/// Workers are stopped by [`shutdown`](#method.shutdown) or when this struct is dropped.
+///
+/// Requires the `runtime` feature.
+///
+/// ```no_run
+/// use tokio_tun::{QueueWorkers, Tun};
+///
+/// let workers = QueueWorkers::spawn(Tun::builder().name("tun0").queues(4).up(), |queue, tun, packet| {
+///     println!("queue {queue}: {} bytes", packet.len());
+///     // Echo the packet back to the device.
+///     let _ = tun.try_send(packet);
+/// })
+/// .unwrap();
+///
+/// // ...
+/// println!("{:?}", workers.stats());
+/// workers.shutdown().unwrap();
+/// ```
+pub struct QueueWorkers {
+    name: String,
+    workers: Vec<Worker>,
+    counters: Arc<[Counters]>,
+}
+
+impl QueueWorkers {
+    /// Builds a multi-queue device from `builder` (see
+    /// [`TunBuilder::queues`](struct.TunBuilder.html#method.queues)) and spawns one worker per
+    /// queue running `handler` for every received packet.
+    ///
+    /// The handler receives the index of the queue, the queue itself (e.g. to send replies using
+    /// [`Tun::try_send`](struct.Tun.html#method.try_send)) and the packet.
+    ///
+    /// Workers are pinned to the CPUs the process is allowed to run on, in a round-robin order.
+    /// This does not require a tokio runtime.
+    pub fn spawn<F>(builder: TunBuilder, handler: F) -> Result<Self>
+    where
+        F: Fn(usize, &Tun, &[u8]) + Send + Sync + 'static,
+    {
+        let (params, queues) = builder.into_mq_params()?;
+        let (iface, fds) = Tun::allocate(params, queues)?;
+        let iface = Arc::new(iface);
+        let cpus = allowed_cpus()?;
+        let handler = Arc::new(handler);
+        let counters: Arc<[Counters]> = (0..queues).map(|_| Counters::default()).collect();
+
+        let mut this = Self {
+            name: iface.name().to_owned(),
+            workers: Vec::with_capacity(queues),
+            counters,
+        };
+        for (index, fd) in fds.into_iter().enumerate() {
+            let cpu = cpus[index % cpus.len()];
+            let iface = iface.clone();
+            let (tx, rx) = oneshot::channel();
+            let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+            let handler = handler.clone();
+            let counters = this.counters.clone();
+            let thread = thread::Builder::new()
+                .name(format!("{}-q{}", this.name, index))
+                .spawn(move || {
+                    let (rt, tun) = match setup(cpu, iface, fd) {
+                        Ok(setup) => {
+                            let _ = ready_tx.send(Ok(()));
+                            setup
+                        }
+                        Err(err) => {
+                            let _ = ready_tx.send(Err(err));
+                            return Ok(());
+                        }
+                    };
+                    rt.block_on(run(index, tun, rx, &counters[index], handler.as_ref()))
+                })?;
+            if let Err(err) = ready_rx
+                .recv()
+                .unwrap_or_else(|_| Err(io::Error::other("queue worker panicked").into()))
+            {
+                let _ = thread.join();
+                return Err(err);
+            }
+            this.workers.push(Worker {
+                cpu,
+                shutdown: Some(tx),
+                thread: Some(thread),
+            });
+        }
+        Ok(this)
+    }
+
+    /// Returns the name of Tun/Tap device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of workers (i.e. queues).
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns `true` if there is no worker.
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// Returns the statistics of every worker, ordered by queue index.
+    pub fn stats(&self) -> Vec<WorkerStats> {
+        self.workers
+            .iter()
+            .zip(self.counters.iter())
+            .enumerate()
+            .map(|(queue, (worker, counters))| WorkerStats {
+                queue,
+                cpu: worker.cpu,
+                packets: counters.packets.load(Ordering::Relaxed),
+                bytes: counters.bytes.load(Ordering::Relaxed),
+                errors: counters.errors.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Stops all workers and waits for them to exit. Returns the first error which made a
+    /// worker stop early, if any.
+    pub fn shutdown(mut self) -> Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        for worker in &mut self.workers {
+            if let Some(tx) = worker.shutdown.take() {
+                let _ = tx.send(());
+            }
+        }
+        let mut result = Ok(());
+        for worker in mem::take(&mut self.workers) {
+            let res = match worker.thread.map(JoinHandle::join) {
+                Some(Ok(res)) => res,
+                Some(Err(_)) => Err(io::Error::other("queue worker panicked")),
+                None => Ok(()),
+            };
+            if let (Ok(()), Err(err)) = (&result, res) {
+                result = Err(err.into());
+            }
+        }
+        result
+    }
+}
+
+impl Drop for QueueWorkers {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Pins the worker thread to `cpu`, before its runtime starts so that no packet is handled on
+/// another CPU, and registers its queue with a new runtime.
+fn setup(cpu: usize, iface: Arc<Interface>, fd: OwnedFd) -> Result<(Runtime, Tun)> {
+    pin(cpu)?;
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?;
+    let tun = {
+        let _guard = rt.enter();
+        Tun::from_queue(iface, fd)?
+    };
+    Ok((rt, tun))
+}
+
+async fn run<F>(
+    index: usize,
+    tun: Tun,
+    mut shutdown: oneshot::Receiver<()>,
+    counters: &Counters,
+    handler: &F,
+) -> io::Result<()>
+where
+    F: Fn(usize, &Tun, &[u8]),
+{
+    let mut buf = vec![0u8; BUF_LEN];
+    loop {
+        let res = poll_fn(|cx| {
+            if Pin::new(&mut shutdown).poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+            tun.poll_recv(cx, &mut buf).map(Some)
+        })
+        .await;
+        match res {
+            None => return Ok(()),
+            Some(Ok(n)) => {
+                counters.packets.fetch_add(1, Ordering::Relaxed);
+                counters.bytes.fetch_add(n as u64, Ordering::Relaxed);
+                handler(index, &tun, &buf[..n]);
+            }
+            Some(Err(err)) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Some(Err(err)) => {
+                counters.errors.fetch_add(1, Ordering::Relaxed);
+                return Err(err);
+            }
+        }
+    }
+}
+
+/// Returns the CPUs the current process is allowed to run on.
+fn allowed_cpus() -> io::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    if unsafe { libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let cpus = (0..libc::CPU_SETSIZE as usize)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect::<Vec<_>>();
+    if cpus.is_empty() {
+        return Err(io::Error::other("no CPU available"));
+    }
+    Ok(cpus)
+}
+
+/// Pins the calling thread to `cpu`.
+fn pin(cpu: usize) -> io::Result<()> {
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+    if unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
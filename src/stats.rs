@@ -0,0 +1,161 @@
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Represents a snapshot of the statistics the kernel keeps for a device, returned by
+/// [`Tun::stats`](struct.Tun.html#method.stats).
+///
+/// The counters are seen from the interface, so packets read from the queues by the process are
+/// counted as transmitted (`tx_*`) and packets written to the queues as received (`rx_*`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceStats {
+    /// Number of packets received, i.e. written by the process.
+    pub rx_packets: u64,
+    /// Number of packets transmitted, i.e. queued for the process.
+    pub tx_packets: u64,
+    /// Number of bytes received.
+    pub rx_bytes: u64,
+    /// Number of bytes transmitted.
+    pub tx_bytes: u64,
+    /// Number of malformed packets received.
+    pub rx_errors: u64,
+    /// Number of packets which could not be transmitted.
+    pub tx_errors: u64,
+    /// Number of received packets dropped, e.g. because the device was down.
+    pub rx_dropped: u64,
+    /// Number of packets dropped before transmission, e.g. because a queue was full.
+    pub tx_dropped: u64,
+    /// Number of multicast packets received.
+    pub multicast: u64,
+    /// Number of collisions, always `0` for Tun/Tap devices.
+    pub collisions: u64,
+}
+
+impl InterfaceStats {
+    /// Names of the fields in the order of `struct rtnl_link_stats64`, which are also the names
+    /// of the files in `/sys/class/net/<name>/statistics`.
+    pub(crate) const FIELDS: [&'static str; 10] = [
+        "rx_packets",
+        "tx_packets",
+        "rx_bytes",
+        "tx_bytes",
+        "rx_errors",
+        "tx_errors",
+        "rx_dropped",
+        "tx_dropped",
+        "multicast",
+        "collisions",
+    ];
+
+    /// Creates an instance from the values of [`FIELDS`](#associatedconstant.FIELDS).
+    pub(crate) fn from_values(values: [u64; 10]) -> Self {
+        let [
+            rx_packets,
+            tx_packets,
+            rx_bytes,
+            tx_bytes,
+            rx_errors,
+            tx_errors,
+            rx_dropped,
+            tx_dropped,
+            multicast,
+            collisions,
+        ] = values;
+        Self {
+            rx_packets,
+            tx_packets,
+            rx_bytes,
+            tx_bytes,
+            rx_errors,
+            tx_errors,
+            rx_dropped,
+            tx_dropped,
+            multicast,
+            collisions,
+        }
+    }
+}
+
+/// Represents a snapshot of the counters a queue keeps in the process, returned by
+/// [`Tun::queue_stats`](struct.Tun.html#method.queue_stats).
+///
+/// Unlike [`InterfaceStats`](struct.InterfaceStats.html), the counters are seen from the process:
+/// `rx_*` counts the packets read from the queue and `tx_*` the packets written to it. Byte counts
+/// include the packet information and vnet headers, if enabled. Errors do not include
+/// `WouldBlock` and `Interrupted`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Number of packets read.
+    pub rx_packets: u64,
+    /// Number of bytes read.
+    pub rx_bytes: u64,
+    /// Number of failed reads.
+    pub rx_errors: u64,
+    /// Number of packets written.
+    pub tx_packets: u64,
+    /// Number of bytes written.
+    pub tx_bytes: u64,
+    /// Number of failed writes.
+    pub tx_errors: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct QueueCounters {
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    rx_errors: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_errors: AtomicU64,
+}
+
+impl QueueCounters {
+    /// Counts the result of a read, if `counters` are enabled.
+    pub fn rx(counters: Option<&Self>, res: io::Result<usize>) -> io::Result<usize> {
+        if let Some(counters) = counters {
+            Self::count(
+                &res,
+                &counters.rx_packets,
+                &counters.rx_bytes,
+                &counters.rx_errors,
+            );
+        }
+        res
+    }
+
+    /// Counts the result of a write, if `counters` are enabled.
+    pub fn tx(counters: Option<&Self>, res: io::Result<usize>) -> io::Result<usize> {
+        if let Some(counters) = counters {
+            Self::count(
+                &res,
+                &counters.tx_packets,
+                &counters.tx_bytes,
+                &counters.tx_errors,
+            );
+        }
+        res
+    }
+
+    fn count(res: &io::Result<usize>, packets: &AtomicU64, bytes: &AtomicU64, errors: &AtomicU64) {
+        match res {
+            Ok(n) => {
+                packets.fetch_add(1, Ordering::Relaxed);
+                bytes.fetch_add(*n as u64, Ordering::Relaxed);
+            }
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {}
+            Err(_) => {
+                errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> QueueStats {
+        QueueStats {
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            rx_errors: self.rx_errors.load(Ordering::Relaxed),
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            tx_errors: self.tx_errors.load(Ordering::Relaxed),
+        }
+    }
+}
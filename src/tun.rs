@@ -1,21 +1,25 @@
-use crate::Result;
-use crate::TunBuilder;
-use crate::linux::interface::Interface;
+use crate::gso::Coalescer;
+use crate::linux::interface::{self, Interface};
 use crate::linux::io::TunIo;
 use crate::linux::params::Params;
-use std::io::{self, ErrorKind, IoSlice, Read, Write};
+use crate::linux::scm;
+use crate::netns::NetNsGuard;
+use crate::stats::QueueCounters;
+use crate::{
+    Error, InterfaceEvents, InterfaceFlags, InterfaceStats, IpNetwork, NetNs, OffloadFlags,
+    QueueStats, Result, Route, TunBuilder, VirtioNetHdr,
+};
+use std::io::{self, ErrorKind, IoSlice, IoSliceMut, Read, Write};
 use std::mem;
-use std::net::Ipv4Addr;
-use std::os::raw::c_char;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{self, Context, Poll};
 use tokio::io::unix::AsyncFd;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-static TUN: &[u8] = b"/dev/net/tun\0";
-
 // Taken from the `futures` crate
 macro_rules! ready {
     ($e:expr $(,)?) => {
@@ -26,10 +30,37 @@ macro_rules! ready {
     };
 }
 
+/// Registers a queue file descriptor with the tokio reactor.
+fn register(fd: OwnedFd) -> io::Result<AsyncFd<TunIo>> {
+    // SAFETY: `TunIo` owns `fd` and keeps it open until it is dropped.
+    unsafe { AsyncFd::register(TunIo::from(fd)) }.map_err(|err| err.into_parts().1)
+}
+
+/// Deletes the persistent Tun/Tap device `name`, e.g. one built with
+/// [`TunBuilder::persist`](struct.TunBuilder.html#method.persist).
+///
+/// The persistent flag of the device is cleared, so the device is removed by the kernel as soon
+/// as no process has it open anymore. This requires the same privileges as attaching to the
+/// device, i.e. either `CAP_NET_ADMIN` or being its owner or group.
+///
+/// A single-queue device which is open in another process cannot be attached to. It is deleted
+/// right away using netlink instead, which requires `CAP_NET_ADMIN` and makes further reads and
+/// writes of that process fail.
+pub fn delete_interface(name: &str) -> Result<()> {
+    interface::validate_name(name, false)?;
+    let fd = interface::open(true)?;
+    match interface::unpersist(fd.as_fd(), name) {
+        Err(Error::DeviceBusy { .. }) => interface::delete_link(name),
+        res => res,
+    }
+}
+
 /// Represents a Tun/Tap device. Use [`TunBuilder`](struct.TunBuilder.html) to create a new instance of [`Tun`](struct.Tun.html).
 pub struct Tun {
     iface: Arc<Interface>,
     io: AsyncFd<TunIo>,
+    /// `None` unless enabled using `TunBuilder::queue_stats`.
+    counters: Option<QueueCounters>,
 }
 
 impl AsRawFd for Tun {
@@ -38,6 +69,25 @@ impl AsRawFd for Tun {
     }
 }
 
+impl AsFd for Tun {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.io.get_ref().as_fd()
+    }
+}
+
+impl IntoRawFd for Tun {
+    fn into_raw_fd(self) -> RawFd {
+        OwnedFd::from(self).into_raw_fd()
+    }
+}
+
+impl From<Tun> for OwnedFd {
+    /// Deregisters the queue from the tokio reactor and returns its file descriptor.
+    fn from(tun: Tun) -> Self {
+        tun.io.into_inner().into()
+    }
+}
+
 impl AsyncRead for Tun {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -47,8 +97,11 @@ impl AsyncRead for Tun {
         let self_mut = self.get_mut();
         loop {
             let mut guard = ready!(self_mut.io.poll_read_ready_mut(cx))?;
+            let counters = self_mut.counters.as_ref();
 
-            match guard.try_io(|inner| inner.get_mut().read(buf.initialize_unfilled())) {
+            match guard.try_io(|inner| {
+                QueueCounters::rx(counters, inner.get_mut().read(buf.initialize_unfilled()))
+            }) {
                 Ok(Ok(n)) => {
                     buf.set_filled(buf.filled().len() + n);
                     return Poll::Ready(Ok(()));
@@ -69,8 +122,9 @@ impl AsyncWrite for Tun {
         let self_mut = self.get_mut();
         loop {
             let mut guard = ready!(self_mut.io.poll_write_ready_mut(cx))?;
+            let counters = self_mut.counters.as_ref();
 
-            match guard.try_io(|inner| inner.get_mut().write(buf)) {
+            match guard.try_io(|inner| QueueCounters::tx(counters, inner.get_mut().write(buf))) {
                 Ok(result) => return Poll::Ready(result),
                 Err(_would_block) => continue,
             }
@@ -85,8 +139,11 @@ impl AsyncWrite for Tun {
         let self_mut = self.get_mut();
         loop {
             let mut guard = ready!(self_mut.io.poll_write_ready_mut(cx))?;
+            let counters = self_mut.counters.as_ref();
 
-            match guard.try_io(|inner| inner.get_mut().write_vectored(bufs)) {
+            match guard
+                .try_io(|inner| QueueCounters::tx(counters, inner.get_mut().write_vectored(bufs)))
+            {
                 Ok(result) => return Poll::Ready(result),
                 Err(_would_block) => continue,
             }
@@ -121,51 +178,199 @@ impl Tun {
 
     /// Creates a new instance of Tun/Tap device.
     pub(crate) fn new(params: Params) -> Result<Self> {
-        let iface = Self::allocate(params, 1)?;
-        let fd = iface.files()[0];
-        Ok(Self {
-            iface: Arc::new(iface),
-            io: AsyncFd::new(TunIo::from(fd))?,
-        })
+        let (iface, mut fds) = Self::allocate(params, 1)?;
+        Self::from_queue(Arc::new(iface), fds.remove(0))
     }
 
     /// Creates a new instance of Tun/Tap device.
     pub(crate) fn new_mq(params: Params, queues: usize) -> Result<Vec<Self>> {
-        let iface = Self::allocate(params, queues)?;
-        let mut tuns = Vec::with_capacity(queues);
+        let (iface, fds) = Self::allocate(params, queues)?;
         let iface = Arc::new(iface);
-        for &fd in iface.files() {
-            tuns.push(Self {
-                iface: iface.clone(),
-                io: AsyncFd::new(TunIo::from(fd))?,
-            })
-        }
-        Ok(tuns)
+        fds.into_iter()
+            .map(|fd| Self::from_queue(iface.clone(), fd))
+            .collect()
+    }
+
+    /// Creates a new instance from a queue of an allocated interface, registering it with the
+    /// current tokio runtime.
+    pub(crate) fn from_queue(iface: Arc<Interface>, fd: OwnedFd) -> Result<Self> {
+        Ok(Self {
+            counters: iface.has_queue_stats().then(QueueCounters::default),
+            iface,
+            io: register(fd)?,
+        })
     }
 
-    fn allocate(params: Params, queues: usize) -> Result<Interface> {
-        let extra_flags = if params.cloexec { libc::O_CLOEXEC } else { 0 };
+    /// Opens `queues` queues of the device described by `params`, creating and configuring it
+    /// unless attaching to an existing one.
+    pub(crate) fn allocate(mut params: Params, queues: usize) -> Result<(Interface, Vec<OwnedFd>)> {
+        let netns = params.netns.take();
+        if params.attach {
+            let name = params.name.as_deref().unwrap_or_default();
+            interface::validate_name(name, false)?;
+            let mut flags = params.flags;
+            if queues > 1 {
+                flags |= libc::IFF_MULTI_QUEUE as i16;
+            }
+            // sysfs shows the devices of the namespace it was mounted in. `TUNSETIFF` still
+            // rejects a mismatching type or queue mode, but not mismatching framing flags.
+            if netns.is_none() {
+                interface::check_device(name, flags)?;
+            }
+        } else if let Some(name) = &params.name {
+            interface::validate_name(name, true)?;
+        }
+
+        // The queues and the control sockets belong to the namespace they are created in.
+        let _guard = match &netns {
+            Some(netns) => {
+                let fd = netns.open().map_err(Error::NetNs)?;
+                Some(NetNsGuard::enter(fd.as_fd()).map_err(Error::NetNs)?)
+            }
+            None => None,
+        };
 
         let fds = (0..queues)
-            .map(|_| unsafe {
-                match libc::open(
-                    TUN.as_ptr().cast::<c_char>(),
-                    libc::O_RDWR | libc::O_NONBLOCK | extra_flags,
-                ) {
-                    fd if fd >= 0 => Ok(fd),
-                    _ => Err(io::Error::last_os_error().into()),
-                }
-            })
+            .map(|_| interface::open(params.cloexec))
             .collect::<Result<Vec<_>>>()?;
 
-        let iface = Interface::new(
-            fds,
+        let mut iface = Interface::new(
+            &fds.iter().map(AsFd::as_fd).collect::<Vec<_>>(),
             params.name.as_deref().unwrap_or_default(),
             params.flags,
             params.cloexec,
         )?;
-        iface.init(params)?;
-        Ok(iface)
+        iface.set_queue_stats(params.queue_stats);
+        if !params.attach {
+            iface.init(fds[0].as_fd(), params)?;
+        }
+        Ok((iface, fds))
+    }
+
+    /// Attaches a single queue to the existing (e.g. persistent) device `name`, without
+    /// reconfiguring it.
+    ///
+    /// Unlike [`TunBuilder::attach`](struct.TunBuilder.html#method.attach), the type, queue mode,
+    /// packet information and vnet header flags are taken from the device itself.
+    pub fn attach(name: &str) -> Result<Self> {
+        interface::validate_name(name, false)?;
+        let flags = interface::device_flags(name)? & interface::ATTACH_FLAGS;
+        let fd = interface::open(true)?;
+        let iface = Interface::new(&[fd.as_fd()], name, flags, true)?;
+        Self::from_queue(Arc::new(iface), fd)
+    }
+
+    /// Creates an instance from an already open and attached file descriptor of a Tun/Tap device,
+    /// e.g. one received from a privileged process or a VPN launcher.
+    ///
+    /// The name and flags of the device are queried using `TUNGETIFF` and the file descriptor is
+    /// switched to non-blocking mode. Must be called within a tokio runtime.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self> {
+        let mut tuns = Self::from_fds(vec![fd])?;
+        Ok(tuns.remove(0))
+    }
+
+    /// Receives the queues of a device sent by another process using
+    /// [`TunBuilder::send_to`](struct.TunBuilder.html#method.send_to).
+    ///
+    /// This allows a privileged helper to create the device, while the process handling the
+    /// packets runs without `CAP_NET_ADMIN`. Like [`from_fd`](#method.from_fd), the returned
+    /// instances are fully functional and must be created within a tokio runtime. Note that this
+    /// blocks until the queues are received, unless `stream` is in non-blocking mode.
+    ///
+    /// ```no_run
+    /// use std::os::unix::net::UnixStream;
+    /// use tokio_tun::Tun;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (helper, client) = UnixStream::pair().unwrap();
+    /// // Usually done by a privileged process on the other end of the socket.
+    /// Tun::builder().name("tun0").queues(2).up().send_to(&helper).unwrap();
+    ///
+    /// let queues = Tun::receive_from(&client).unwrap();
+    /// assert_eq!(queues.len(), 2);
+    /// # }
+    /// ```
+    pub fn receive_from(stream: &UnixStream) -> Result<Vec<Self>> {
+        let (metadata, fds) = scm::recv(stream)?;
+        let tuns = Self::from_fds(fds)?;
+        if tuns[0].name() != metadata.name {
+            return Err(Error::InvalidDevice {
+                interface: tuns[0].name().to_owned(),
+                reason: format!("expected the queues of `{}`", metadata.name),
+            });
+        }
+        Ok(tuns)
+    }
+
+    fn from_fds(fds: Vec<OwnedFd>) -> Result<Vec<Self>> {
+        for fd in &fds {
+            let fd = fd.as_raw_fd();
+            match unsafe { libc::fcntl(fd, libc::F_GETFL) } {
+                flags if flags >= 0 => {
+                    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+                        return Err(io::Error::last_os_error().into());
+                    }
+                }
+                _ => return Err(io::Error::last_os_error().into()),
+            }
+        }
+        let iface = Arc::new(Interface::from_fds(
+            &fds.iter().map(AsFd::as_fd).collect::<Vec<_>>(),
+        )?);
+        fds.into_iter()
+            .map(|fd| Self::from_queue(iface.clone(), fd))
+            .collect()
+    }
+
+    /// Allocates a device and sends its queues along with its name over `stream`, closing them
+    /// in this process.
+    pub(crate) fn send_to(params: Params, queues: usize, stream: &UnixStream) -> Result<()> {
+        let (iface, fds) = Self::allocate(params, queues)?;
+        // The device lives on in the receiving process.
+        iface.remove_routes_on_drop(false);
+        let metadata = scm::Metadata {
+            name: iface.name().to_owned(),
+            queues: fds.len(),
+        };
+        scm::send(stream, &metadata, &fds)
+    }
+
+    /// Opens an additional queue of a multi-queue device, i.e. a device built with
+    /// [`TunBuilder::queues`](struct.TunBuilder.html#method.queues) greater than `1`.
+    ///
+    /// The returned instance shares the interface with `self` and is attached on creation. The
+    /// queue is opened in the network namespace of the device, i.e. the one given to
+    /// [`TunBuilder::netns`](struct.TunBuilder.html#method.netns) or
+    /// [`move_to_netns`](#method.move_to_netns), which requires `CAP_SYS_ADMIN` if it is not the
+    /// one of the calling thread.
+    pub fn new_queue(&self) -> Result<Self> {
+        if !self.iface.is_multi_queue() {
+            return Err(Error::Unsupported {
+                op: "opening a queue",
+                interface: self.name().to_owned(),
+                reason: "not a multi-queue device",
+            });
+        }
+        let fd = self.iface.open_queue()?;
+        Self::from_queue(self.iface.clone(), fd)
+    }
+
+    /// Attaches the queue of this instance to the multi-queue device (`IFF_ATTACH_QUEUE`).
+    ///
+    /// Queues are attached on creation, this re-enables a queue disabled by
+    /// [`detach_queue`](#method.detach_queue).
+    pub fn attach_queue(&self) -> Result<()> {
+        self.iface.set_queue(self.as_fd(), true)
+    }
+
+    /// Detaches the queue of this instance from the multi-queue device (`IFF_DETACH_QUEUE`).
+    ///
+    /// The kernel stops delivering packets to a detached queue, while the file descriptor stays
+    /// open so that the queue can be re-enabled using [`attach_queue`](#method.attach_queue).
+    pub fn detach_queue(&self) -> Result<()> {
+        self.iface.set_queue(self.as_fd(), false)
     }
 
     /// Receives a packet from the Tun/Tap interface.
@@ -174,20 +379,34 @@ impl Tun {
     pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
         loop {
             let mut guard = self.io.readable().await?;
-            match guard.try_io(|inner| inner.get_ref().recv(buf)) {
+            match guard.try_io(|inner| self.count_rx(inner.get_ref().recv(buf))) {
                 Ok(res) => return res,
                 Err(_) => continue,
             }
         }
     }
 
+    /// Attempts to receive a packet from the Tun/Tap interface.
+    ///
+    /// If no packet is available, `Poll::Pending` is returned and the waker of `cx` is notified
+    /// once the device becomes readable.
+    pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.io.poll_read_ready(cx))?;
+            match guard.try_io(|inner| self.count_rx(inner.get_ref().recv(buf))) {
+                Ok(res) => return Poll::Ready(res),
+                Err(_) => continue,
+            }
+        }
+    }
+
     /// Sends a buffer to the Tun/Tap interface. Returns the number of bytes written to the device.
     ///
     /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
     pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
         loop {
             let mut guard = self.io.writable().await?;
-            match guard.try_io(|inner| inner.get_ref().send(buf)) {
+            match guard.try_io(|inner| self.count_tx(inner.get_ref().send(buf))) {
                 Ok(res) => return res,
                 Err(_) => continue,
             }
@@ -217,7 +436,7 @@ impl Tun {
     pub async fn sendv(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
         loop {
             let mut guard = self.io.writable().await?;
-            match guard.try_io(|inner| inner.get_ref().sendv(bufs)) {
+            match guard.try_io(|inner| self.count_tx(inner.get_ref().sendv(bufs))) {
                 Ok(res) => return res,
                 Err(_) => continue,
             }
@@ -254,13 +473,81 @@ impl Tun {
         Ok(())
     }
 
+    /// Receives a packet along with its vnet header from the Tun/Tap interface. Returns the header
+    /// and the number of bytes written to `buf`, excluding the header.
+    ///
+    /// The device must be built with [`TunBuilder::vnet_hdr`](struct.TunBuilder.html#method.vnet_hdr).
+    ///
+    /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
+    pub async fn recv_vnet(&self, buf: &mut [u8]) -> io::Result<(VirtioNetHdr, usize)> {
+        self.check_vnet_hdr()?;
+        let mut hdr = [0u8; VirtioNetHdr::LEN];
+        let n = loop {
+            let mut guard = self.io.readable().await?;
+            let mut bufs = [IoSliceMut::new(&mut hdr), IoSliceMut::new(buf)];
+            match guard.try_io(|inner| self.count_rx(inner.get_ref().recvv(&mut bufs))) {
+                Ok(res) => break res?,
+                Err(_) => continue,
+            }
+        };
+        let hdr = VirtioNetHdr::decode(&hdr[..n.min(VirtioNetHdr::LEN)])
+            .ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?;
+        Ok((hdr, n - VirtioNetHdr::LEN))
+    }
+
+    /// Sends a packet prefixed by the given vnet header to the Tun/Tap interface. Returns the
+    /// number of bytes of `buf` written to the device.
+    ///
+    /// The device must be built with [`TunBuilder::vnet_hdr`](struct.TunBuilder.html#method.vnet_hdr).
+    ///
+    /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
+    pub async fn send_vnet(&self, hdr: &VirtioNetHdr, buf: &[u8]) -> io::Result<usize> {
+        self.check_vnet_hdr()?;
+        let hdr = hdr.encode();
+        let n = self.sendv(&[IoSlice::new(&hdr), IoSlice::new(buf)]).await?;
+        Ok(n.saturating_sub(VirtioNetHdr::LEN))
+    }
+
+    /// Coalesces consecutive TCP segments of the same flow into GSO super-packets and sends
+    /// them, along with all other packets, to the Tun/Tap interface.
+    ///
+    /// This reduces the number of writes and lets the kernel process large packets at once. See
+    /// [`gso::Coalescer`](gso/struct.Coalescer.html) for details. The device must be built with
+    /// [`TunBuilder::vnet_hdr`](struct.TunBuilder.html#method.vnet_hdr).
+    ///
+    /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
+    pub async fn send_coalesced<'a>(
+        &self,
+        packets: impl IntoIterator<Item = &'a [u8]>,
+    ) -> io::Result<()> {
+        self.check_vnet_hdr()?;
+        let mut coalescer = Coalescer::new();
+        for packet in packets {
+            coalescer.push(packet);
+        }
+        for (hdr, packet) in coalescer.drain() {
+            self.send_vnet(&hdr, &packet).await?;
+        }
+        Ok(())
+    }
+
+    fn check_vnet_hdr(&self) -> io::Result<()> {
+        if !self.iface.has_vnet_hdr() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "vnet headers are not enabled on this device",
+            ));
+        }
+        Ok(())
+    }
+
     /// Tries to receive a buffer from the Tun/Tap interface.
     ///
     /// When there is no pending data, `Err(io::ErrorKind::WouldBlock)` is returned.
     ///
     /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
     pub fn try_recv(&self, buf: &mut [u8]) -> io::Result<usize> {
-        self.io.get_ref().recv(buf)
+        self.count_rx(self.io.get_ref().recv(buf))
     }
 
     /// Tries to send a packet to the Tun/Tap interface.
@@ -269,7 +556,7 @@ impl Tun {
     ///
     /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
     pub fn try_send(&self, buf: &[u8]) -> io::Result<usize> {
-        self.io.get_ref().send(buf)
+        self.count_tx(self.io.get_ref().send(buf))
     }
 
     /// Tries to send several different buffers to the Tun/Tap interface.
@@ -278,7 +565,15 @@ impl Tun {
     ///
     /// This method takes &self, so it is possible to call this method concurrently with other methods on this struct.
     pub fn try_sendv(&self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
-        self.io.get_ref().sendv(bufs)
+        self.count_tx(self.io.get_ref().sendv(bufs))
+    }
+
+    fn count_rx(&self, res: io::Result<usize>) -> io::Result<usize> {
+        QueueCounters::rx(self.counters.as_ref(), res)
+    }
+
+    fn count_tx(&self, res: io::Result<usize>) -> io::Result<usize> {
+        QueueCounters::tx(self.counters.as_ref(), res)
     }
 
     /// Returns the name of Tun/Tap device.
@@ -291,6 +586,39 @@ impl Tun {
         self.iface.mtu(None)
     }
 
+    /// Returns the length of the transmit queue.
+    pub fn txqueuelen(&self) -> Result<u32> {
+        self.iface.txqueuelen(None)
+    }
+
+    /// Returns the statistics the kernel keeps for device, read from netlink (`IFLA_STATS64`) or
+    /// sysfs.
+    ///
+    /// Note that they are seen from the interface: packets received by the process are counted
+    /// as transmitted. See [`InterfaceStats`](struct.InterfaceStats.html).
+    pub fn stats(&self) -> Result<InterfaceStats> {
+        self.iface.stats()
+    }
+
+    /// Returns the counters of this queue kept in the process, or `None` unless the device was
+    /// built with [`TunBuilder::queue_stats`](struct.TunBuilder.html#method.queue_stats).
+    ///
+    /// ```no_run
+    /// use tokio_tun::Tun;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let tun = Tun::builder().name("tun0").queue_stats().up().build().unwrap().pop().unwrap();
+    /// // ...
+    /// let kernel = tun.stats().unwrap();
+    /// let process = tun.queue_stats().unwrap();
+    /// println!("{} packets lost", kernel.tx_packets.saturating_sub(process.rx_packets));
+    /// # }
+    /// ```
+    pub fn queue_stats(&self) -> Option<QueueStats> {
+        self.counters.as_ref().map(QueueCounters::snapshot)
+    }
+
     /// Returns the IPv4 address of MTU.
     pub fn address(&self) -> Result<Ipv4Addr> {
         self.iface.address(None)
@@ -311,8 +639,192 @@ impl Tun {
         self.iface.netmask(None)
     }
 
-    /// Returns the flags of MTU.
-    pub fn flags(&self) -> Result<i16> {
-        self.iface.flags(None)
+    /// Sets the MTU of device.
+    pub fn set_mtu(&self, mtu: i32) -> Result<()> {
+        self.iface.mtu(Some(mtu)).map(|_| ())
+    }
+
+    /// Sets the length of the transmit queue of device.
+    pub fn set_txqueuelen(&self, len: u32) -> Result<()> {
+        self.iface.txqueuelen(Some(len)).map(|_| ())
+    }
+
+    /// Sets the IPv4 address of device.
+    ///
+    /// As with `ifconfig`, changing the address resets the netmask to the one of its class (`/32`
+    /// for Tun devices) and the broadcast address, so set the netmask afterwards.
+    pub fn set_address(&self, address: Ipv4Addr) -> Result<()> {
+        self.iface.address(Some(address)).map(|_| ())
+    }
+
+    /// Sets the IPv4 destination address of device.
+    pub fn set_destination(&self, dst: Ipv4Addr) -> Result<()> {
+        self.iface.destination(Some(dst)).map(|_| ())
+    }
+
+    /// Sets the IPv4 broadcast address of device.
+    pub fn set_broadcast(&self, broadcast: Ipv4Addr) -> Result<()> {
+        self.iface.broadcast(Some(broadcast)).map(|_| ())
+    }
+
+    /// Sets the IPv4 netmask address of device. The broadcast address follows the netmask
+    /// unless it was set explicitly.
+    pub fn set_netmask(&self, netmask: Ipv4Addr) -> Result<()> {
+        self.iface.netmask(Some(netmask)).map(|_| ())
+    }
+
+    /// Moves the device to the network namespace `ns`, given by a path (e.g.
+    /// `/var/run/netns/<name>`) or an open file descriptor.
+    ///
+    /// The queues keep working from the namespace of the caller and the getters and setters
+    /// apply to the device in its new namespace, as do queues opened afterwards using
+    /// [`new_queue`](#method.new_queue). Note that the kernel removes the addresses and routes
+    /// of the device when moving it.
+    pub fn move_to_netns(&self, ns: impl Into<NetNs>) -> Result<()> {
+        let ns = ns.into();
+        let fd = ns.open().map_err(Error::NetNs)?;
+        self.iface.move_to_netns(fd.as_fd())
+    }
+
+    /// Makes the device persistent, or removes its persistence so that it is deleted once all
+    /// its queues are closed.
+    ///
+    /// See [`TunBuilder::persist`](struct.TunBuilder.html#method.persist).
+    pub fn set_persist(&self, persist: bool) -> Result<()> {
+        self.iface.persist(self.as_fd(), persist)
+    }
+
+    /// Sets the offloads which the kernel may use when passing packets to the device.
+    ///
+    /// This requires vnet headers to be enabled using
+    /// [`TunBuilder::vnet_hdr`](struct.TunBuilder.html#method.vnet_hdr).
+    pub fn set_offload(&self, offload: OffloadFlags) -> Result<()> {
+        self.iface.offload(self.as_fd(), offload)
+    }
+
+    /// Returns the MAC address of device.
+    ///
+    /// This is only supported by TAP devices.
+    pub fn mac_address(&self) -> Result<[u8; 6]> {
+        self.iface.mac_address(None)
+    }
+
+    /// Sets the MAC address of device.
+    ///
+    /// This is only supported by TAP devices.
+    pub fn set_mac_address(&self, mac: [u8; 6]) -> Result<()> {
+        self.iface.mac_address(Some(mac)).map(|_| ())
+    }
+
+    /// Returns all IPv6 addresses of device along with their prefix lengths, i.e. the IPv6 part
+    /// of [`addresses`](#method.addresses).
+    pub fn ipv6_addresses(&self) -> Result<Vec<(Ipv6Addr, u8)>> {
+        Ok(self
+            .addresses()?
+            .into_iter()
+            .filter_map(|network| match network.address() {
+                IpAddr::V6(address) => Some((address, network.prefix_len())),
+                IpAddr::V4(_) => None,
+            })
+            .collect())
+    }
+
+    /// Returns all IPv4 and IPv6 addresses of device along with their prefix lengths.
+    pub fn addresses(&self) -> Result<Vec<IpNetwork>> {
+        self.iface.addresses()
+    }
+
+    /// Adds an IPv4 or IPv6 address along with its prefix length to device.
+    ///
+    /// Without netlink, only a single IPv4 address can be added, which becomes the primary
+    /// address, and adding another one fails with `Error::Unsupported`.
+    pub fn add_address(&self, address: IpNetwork) -> Result<()> {
+        self.iface.add_address(address)
+    }
+
+    /// Removes an IPv4 or IPv6 address from device. Without netlink, only the primary IPv4
+    /// address can be removed.
+    pub fn remove_address(&self, address: IpNetwork) -> Result<()> {
+        self.iface.remove_address(address)
+    }
+
+    /// Returns a stream of the changes of device, e.g. when it is brought down, its MTU or
+    /// addresses change or it is deleted by another process.
+    ///
+    /// The stream implements [`Stream`](https://docs.rs/futures-core/latest/futures_core/stream/trait.Stream.html)
+    /// and can also be consumed using
+    /// [`InterfaceEvents::recv`](struct.InterfaceEvents.html#method.recv). Must be called within
+    /// a tokio runtime.
+    ///
+    /// ```no_run
+    /// use tokio_tun::{InterfaceEvent, Tun};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let tun = Tun::builder().name("tun0").up().build().unwrap().pop().unwrap();
+    /// let mut events = tun.events().unwrap();
+    /// while let Some(event) = events.recv().await {
+    ///     match event.unwrap() {
+    ///         InterfaceEvent::Down => eprintln!("{} is down", tun.name()),
+    ///         event => println!("{event:?}"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// Note that the getters and setters of device keep using the name it was created with.
+    pub fn events(&self) -> Result<InterfaceEvents> {
+        let (socket, link) = self.iface.subscribe()?;
+        Ok(InterfaceEvents::new(socket, link)?)
+    }
+
+    /// Returns the IPv4 and IPv6 routes through device, in every table but the local one.
+    ///
+    /// This includes the routes the kernel adds for the addresses of device. Routes in the main
+    /// table have no [`table`](struct.Route.html#structfield.table).
+    pub fn routes(&self) -> Result<Vec<Route>> {
+        self.iface.routes()
+    }
+
+    /// Adds an IPv4 or IPv6 route through device.
+    ///
+    /// The route is removed when the last queue is dropped if the device was built with
+    /// [`TunBuilder::remove_routes_on_drop`](struct.TunBuilder.html#method.remove_routes_on_drop).
+    pub fn add_route(&self, route: impl Into<Route>) -> Result<()> {
+        self.iface.add_route(route.into())
+    }
+
+    /// Removes a route through device.
+    pub fn remove_route(&self, route: impl Into<Route>) -> Result<()> {
+        self.iface.remove_route(route.into())
+    }
+
+    /// Returns the flags of device.
+    pub fn flags(&self) -> Result<InterfaceFlags> {
+        self.iface.flags()
+    }
+
+    /// Sets the given flags on device, leaving the other flags untouched.
+    pub fn set_flags(&self, flags: InterfaceFlags) -> Result<()> {
+        self.iface
+            .update_flags(flags, InterfaceFlags::empty())
+            .map(|_| ())
+    }
+
+    /// Clears the given flags on device, leaving the other flags untouched.
+    pub fn clear_flags(&self, flags: InterfaceFlags) -> Result<()> {
+        self.iface
+            .update_flags(InterfaceFlags::empty(), flags)
+            .map(|_| ())
+    }
+
+    /// Sets up the device, i.e. puts the interface into the *up* state.
+    pub fn up(&self) -> Result<()> {
+        self.set_flags(InterfaceFlags::UP | InterfaceFlags::RUNNING)
+    }
+
+    /// Brings down the device, i.e. puts the interface into the *down* state.
+    pub fn down(&self) -> Result<()> {
+        self.clear_flags(InterfaceFlags::UP)
     }
 }
@@ -0,0 +1,78 @@
+/// Represents the `virtio_net_hdr` which prefixes every packet when vnet headers are enabled
+/// using [`TunBuilder::vnet_hdr`](struct.TunBuilder.html#method.vnet_hdr).
+///
+/// The header describes checksum offload and segmentation (GSO) metadata of the packet. See
+/// the [virtio specification](https://docs.oasis-open.org/virtio/virtio/v1.2/virtio-v1.2.html)
+/// under section *Device Operation* of the network device for an official description.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VirtioNetHdr {
+    /// Combination of `FLAG_*` values.
+    pub flags: u8,
+    /// One of `GSO_*` values, optionally combined with `GSO_ECN`.
+    pub gso_type: u8,
+    /// Length of the headers (L2/L3/L4) to be copied to each segment.
+    pub hdr_len: u16,
+    /// Maximum size of each segment's payload.
+    pub gso_size: u16,
+    /// Offset from which the checksum should be computed.
+    pub csum_start: u16,
+    /// Offset after `csum_start` at which the checksum should be stored.
+    pub csum_offset: u16,
+}
+
+impl VirtioNetHdr {
+    /// Length of the header on the wire.
+    pub const LEN: usize = 10;
+
+    /// The checksum of the packet is partial and must be computed using `csum_start` and
+    /// `csum_offset`.
+    pub const FLAG_NEEDS_CSUM: u8 = 1;
+    /// The checksum of the packet has already been validated.
+    pub const FLAG_DATA_VALID: u8 = 2;
+
+    /// Not a GSO packet.
+    pub const GSO_NONE: u8 = 0;
+    /// GSO packet carrying TCP over IPv4.
+    pub const GSO_TCPV4: u8 = 1;
+    /// GSO packet carrying UDP (fragmentation offload).
+    pub const GSO_UDP: u8 = 3;
+    /// GSO packet carrying TCP over IPv6.
+    pub const GSO_TCPV6: u8 = 4;
+    /// GSO packet carrying UDP segments (segmentation offload).
+    pub const GSO_UDP_L4: u8 = 5;
+    /// The TCP segments have the ECN bit set.
+    pub const GSO_ECN: u8 = 0x80;
+
+    /// Decodes a header from the first [`LEN`](#associatedconstant.LEN) bytes of `buf`.
+    ///
+    /// Returns `None` if `buf` is too short.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let buf: &[u8; Self::LEN] = buf.get(..Self::LEN)?.try_into().ok()?;
+        let u16_at = |i: usize| u16::from_ne_bytes([buf[i], buf[i + 1]]);
+        Some(Self {
+            flags: buf[0],
+            gso_type: buf[1],
+            hdr_len: u16_at(2),
+            gso_size: u16_at(4),
+            csum_start: u16_at(6),
+            csum_offset: u16_at(8),
+        })
+    }
+
+    /// Encodes the header into its wire format.
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut buf = [0u8; Self::LEN];
+        buf[0] = self.flags;
+        buf[1] = self.gso_type;
+        buf[2..4].copy_from_slice(&self.hdr_len.to_ne_bytes());
+        buf[4..6].copy_from_slice(&self.gso_size.to_ne_bytes());
+        buf[6..8].copy_from_slice(&self.csum_start.to_ne_bytes());
+        buf[8..10].copy_from_slice(&self.csum_offset.to_ne_bytes());
+        buf
+    }
+
+    /// Returns `true` if the packet is a GSO super-packet which must be segmented.
+    pub fn is_gso(&self) -> bool {
+        self.gso_type & !Self::GSO_ECN != Self::GSO_NONE
+    }
+}